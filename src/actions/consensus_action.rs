@@ -1,22 +1,31 @@
 use crate::{
     actions::{
-        clean_invisible_chars, complete_dialog, get_pages, pick_save_folder, save_file,
-        select_format_folder, server_action::ServerAction,
+        clean_invisible_chars, complete_dialog, get_pages, load_file, pick_save_folder,
+        report_dialog, save_file, select_format_folder, server_action::ServerAction,
+    },
+    controller::{
+        merge::merge_section, parse::remove_think_tags, part_tag, structure::TextMode,
+        xml::RubyPolicy,
     },
-    controller::{parse::remove_think_tags, part_tag},
     error::{Error, Result},
     message::{display_error, select_epub},
     model::{
         Activity,
-        consensus::{Candidate, Consensus},
-        page::Page,
+        consensus::{
+            Candidate, Consensus, ConsensusMode, ConsensusProject, DiffReference, Generator,
+            Prompt, named_candidates,
+        },
+        page::{CandidateScore, Page, PageError, Section},
+        server::part_candidates,
     },
 };
 use iced::Task;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use regex::Regex;
 use std::{
     collections::HashMap,
     ffi::OsStr,
+    mem,
     path::{Path, PathBuf},
     sync::LazyLock,
 };
@@ -52,6 +61,8 @@ pub enum ConsensusAction {
         pages: Vec<Page>,
     },
     OpenEpub,
+    SetRuby(RubyPolicy),
+    SetTextMode(TextMode),
     CleanText {
         page: usize,
         part: usize,
@@ -63,6 +74,41 @@ pub enum ConsensusAction {
         pages: Vec<(PathBuf, String)>,
     },
     DropCandidate(usize),
+    AddTab(usize),
+    AddGenerator,
+    DropGenerator(usize),
+    SetGeneratorModel(usize, String),
+    SetGeneratorPrompt(usize, Prompt),
+    Generate(usize),
+    GeneratedPage(usize),
+    SetCandidatePart {
+        candidate: usize,
+        page: usize,
+        part: usize,
+        content: String,
+    },
+    SaveRecovery(PathBuf),
+    Recover,
+    SetRecovery(String),
+    SetMode(ConsensusMode),
+    Judge(usize),
+    JudgePart {
+        page: usize,
+        part: usize,
+    },
+    SetScores {
+        page: usize,
+        part: usize,
+        scores: Vec<CandidateScore>,
+    },
+    KeepTopScored,
+    ShowDiff(Option<usize>),
+    SetDiffReference(DiffReference),
+    Merge,
+    MergePart {
+        page: usize,
+        part: usize,
+    },
 }
 
 impl Consensus {
@@ -96,16 +142,31 @@ impl Consensus {
                 page,
                 part,
             } => self.update_content(content, page, part).into(),
-            ConsensusAction::PageComplete(page) => self.check_complete(page).into(),
-            ConsensusAction::SetEpub { path: name, pages } => self.set_epub(name, pages).into(),
-            ConsensusAction::OpenEpub => Task::future(select_epub())
-                .and_then(|(name, buffer)| Task::future(get_pages(name, buffer)))
-                .then(|doc| match doc {
-                    Ok((name, pages)) => Task::done(ConsensusAction::SetEpub { path: name, pages }),
-                    Err(error) => Task::future(display_error(error)).discard(),
-                }),
+            ConsensusAction::PageComplete(page) => {
+                self.check_complete(page);
+                self.refresh_diff().into()
+            }
+            ConsensusAction::SetEpub { path: name, pages } => self.set_epub(name, pages),
+            ConsensusAction::OpenEpub => {
+                let (ruby, mode) = (self.ruby, self.text_mode);
+                Task::future(select_epub())
+                    .and_then(move |(name, buffer)| {
+                        Task::future(get_pages(name, buffer, ruby, mode))
+                    })
+                    .then(|doc| match doc {
+                        Ok((name, pages)) => {
+                            Task::done(ConsensusAction::SetEpub { path: name, pages })
+                        }
+                        Err(error) => Task::future(display_error(error)).discard(),
+                    })
+            }
+            ConsensusAction::SetRuby(ruby) => self.set_ruby(ruby).into(),
+            ConsensusAction::SetTextMode(mode) => self.set_text_mode(mode).into(),
             ConsensusAction::CancelConsensus => self.cancel().into(),
-            ConsensusAction::SetPage(page) => self.set_page(page).into(),
+            ConsensusAction::SetPage(page) => {
+                self.set_page(page);
+                self.refresh_diff().into()
+            }
             ConsensusAction::SelectCandidate(i) => Task::future(select_format_folder(
                 self.file_path.parent().unwrap_or(Path::new("")).into(),
             ))
@@ -113,11 +174,420 @@ impl Consensus {
                 Task::done(ConsensusAction::SetCandidate { i, name, pages })
             }),
             ConsensusAction::SetCandidate { i, name, pages } => {
-                self.set_candidate(i, name, pages).into()
+                let task = self.set_candidate(i, name, pages);
+                self.refresh_diff();
+                task
             }
             ConsensusAction::CleanText { page, part } => self.clean_text(page, part).into(),
-            ConsensusAction::DropCandidate(i) => self.drop_candidate(i).into(),
+            ConsensusAction::AddTab(tab) => self.add_tab(tab).into(),
+            ConsensusAction::DropCandidate(i) => {
+                self.drop_candidate(i);
+                self.refresh_diff().into()
+            }
+            ConsensusAction::AddGenerator => self.add_generator().into(),
+            ConsensusAction::DropGenerator(i) => self.drop_generator(i).into(),
+            ConsensusAction::SetGeneratorModel(i, model) => {
+                self.set_generator_model(i, model).into()
+            }
+            ConsensusAction::SetGeneratorPrompt(i, prompt) => {
+                self.set_generator_prompt(i, prompt).into()
+            }
+            ConsensusAction::Generate(page) => self
+                .generate(page)
+                .unwrap_or_else(|error| Task::future(display_error(error)).discard()),
+            ConsensusAction::GeneratedPage(page) => self
+                .generated_page(page)
+                .unwrap_or_else(|error| Task::future(display_error(error)).discard()),
+            ConsensusAction::SetCandidatePart {
+                candidate,
+                page,
+                part,
+                content,
+            } => self
+                .set_candidate_part(candidate, page, part, content)
+                .into(),
+            ConsensusAction::SaveRecovery(path) => {
+                self.save_json(path).unwrap_or_else(Error::display_error)
+            }
+            ConsensusAction::Recover => Task::future(load_file("recovery", "json", &["json"]))
+                .and_then(|json| Task::done(ConsensusAction::SetRecovery(json))),
+            ConsensusAction::SetRecovery(json) => match self.recover(&json) {
+                Ok(_) => Task::none(),
+                Err(error) => error.display_error(),
+            },
+            ConsensusAction::SetMode(mode) => self.set_mode(mode).into(),
+            ConsensusAction::Judge(page) => self
+                .judge(page)
+                .unwrap_or_else(|error| Task::future(display_error(error)).discard()),
+            ConsensusAction::JudgePart { page, part } => self
+                .judge_part(page, part)
+                .unwrap_or_else(|error| Task::future(display_error(error)).discard()),
+            ConsensusAction::KeepTopScored => self.keep_top_scored().into(),
+            ConsensusAction::SetScores { page, part, scores } => {
+                self.set_scores(page, part, scores);
+                self.refresh_diff().into()
+            }
+            ConsensusAction::ShowDiff(part) => self.show_diff(part).into(),
+            ConsensusAction::SetDiffReference(reference) => {
+                self.set_diff_reference(reference).into()
+            }
+            ConsensusAction::Merge => {
+                let parts = self
+                    .pages
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(page, p)| (0..p.sections.len()).map(move |part| (page, part)))
+                    .collect();
+                self.merge(parts).unwrap_or_else(Error::display_error)
+            }
+            ConsensusAction::MergePart { page, part } => self
+                .merge(vec![(page, part)])
+                .unwrap_or_else(Error::display_error),
+        }
+    }
+
+    /// statistical consensus of the given parts, merged in parallel from the candidate sentences
+    fn merge(&mut self, parts: Vec<(usize, usize)>) -> Result<Task<ConsensusAction>> {
+        if self.candidates.is_empty() {
+            return Err(Error::Error(String::from("No candidates loaded")));
+        }
+
+        let merged: Vec<_> = parts
+            .par_iter()
+            .filter_map(|&(page, part)| {
+                let candidates = named_candidates(&self.candidates, self.pages.get(page)?);
+                let texts: Vec<_> = candidates
+                    .into_iter()
+                    .filter_map(|(name, parts)| Some((name, parts.get(part)?.as_str())))
+                    .filter(|(_, text)| !text.trim().is_empty())
+                    .collect();
+                Some((page, part, merge_section(&texts)))
+            })
+            .collect();
+
+        let mut pages = Vec::new();
+        for (page, part, merged) in merged {
+            let current = &mut self.pages[page];
+            match merged {
+                // the section keeps what it had when there is nothing to merge
+                None => current.mark_missing(part),
+                Some((content, sources)) => {
+                    current
+                        .errors
+                        .retain(|e| !matches!(e, PageError::Missing(i) if *i == part));
+                    if let Some(section) = current.sections.get_mut(part) {
+                        section.content = content;
+                        section.sources = sources;
+                    }
+                }
+            }
+            if pages.last() != Some(&page) {
+                pages.push(page);
+            }
+        }
+        for page in pages {
+            self.check_complete(page);
+        }
+        self.refresh_diff();
+
+        Ok(Task::none())
+    }
+
+    fn show_diff(&mut self, part: Option<usize>) {
+        let reference = self
+            .diff
+            .take()
+            .map(|diff| diff.reference)
+            .unwrap_or_default();
+        self.diff = part.and_then(|part| {
+            self.build_diff(part, reference)
+                .or_else(|| self.build_diff(part, DiffReference::Consensus))
+        });
+    }
+
+    fn set_diff_reference(&mut self, reference: DiffReference) {
+        if let Some(part) = self.diff.as_ref().map(|diff| diff.part) {
+            self.diff = self.build_diff(part, reference);
+        }
+    }
+
+    /// rebuilds an open diff after the page or its texts change
+    fn refresh_diff(&mut self) {
+        let part = self.diff.as_ref().map(|diff| {
+            let sections = self.current_page().map_or(0, |p| p.sections.len());
+            diff.part.min(sections.saturating_sub(1))
+        });
+        self.show_diff(part);
+    }
+
+    fn set_ruby(&mut self, ruby: RubyPolicy) {
+        self.ruby = ruby;
+    }
+
+    fn set_text_mode(&mut self, mode: TextMode) {
+        self.text_mode = mode;
+    }
+
+    fn set_mode(&mut self, mode: ConsensusMode) {
+        self.mode = mode;
+    }
+
+    pub fn judge(&mut self, page: usize) -> Result<Task<ConsensusAction>> {
+        let model = self.check_ready()?;
+        let Some(current) = self.pages.get_mut(page) else {
+            let file_name = self.file_name();
+            self.server.abort();
+            return Ok(Task::future(complete_dialog(file_name)).discard());
+        };
+        current.activity = Activity::Active;
+        current.sections.iter_mut().for_each(|s| s.scores.clear());
+
+        let current = &self.pages[page];
+        let candidates = named_candidates(&self.candidates, current);
+        let parts = 0..current.sections.len();
+        let task = self
+            .server
+            .judge(current, &candidates, &model, page, parts)?;
+        let complete_task = self.complete_task(page);
+        let next_task = self
+            .server
+            .bind_handle(Task::done(ConsensusAction::Judge(page + 1)));
+
+        Ok(task.chain(complete_task).chain(next_task))
+    }
+
+    pub fn judge_part(&mut self, page: usize, part: usize) -> Result<Task<ConsensusAction>> {
+        let model = self.check_ready()?;
+        let Some(current) = self.pages.get_mut(page) else {
+            return Ok(Task::done(ServerAction::Abort.into()));
+        };
+        current.activity = Activity::Active;
+        if let Some(section) = current.sections.get_mut(part) {
+            section.scores.clear();
+        }
+
+        let current = &self.pages[page];
+        let candidates = named_candidates(&self.candidates, current);
+        let task = self
+            .server
+            .judge(current, &candidates, &model, page, part..part + 1)?;
+
+        Ok(task
+            .chain(self.complete_task(page))
+            .chain(Task::done(ServerAction::Abort.into())))
+    }
+
+    fn set_scores(&mut self, page: usize, part: usize, scores: Vec<CandidateScore>) {
+        if let Some(section) = self
+            .pages
+            .get_mut(page)
+            .and_then(|p| p.sections.get_mut(part))
+        {
+            section.scores = scores;
+        }
+    }
+
+    /// replaces the content of every judged section by its top scoring candidate
+    fn keep_top_scored(&mut self) {
+        let mut pages = Vec::new();
+        for (page, current) in self.pages.iter_mut().enumerate() {
+            let candidates = named_candidates(&self.candidates, current);
+            let mut kept = false;
+            for (part, section) in current.sections.iter_mut().enumerate() {
+                let Some(top) = section
+                    .scores
+                    .iter()
+                    .max_by(|a, b| a.total().total_cmp(&b.total()))
+                else {
+                    continue;
+                };
+                let text = candidates
+                    .iter()
+                    .find(|(name, _)| *name == top.name)
+                    .and_then(|(_, parts)| parts.get(part))
+                    .filter(|text| !text.trim().is_empty());
+                if let Some(text) = text {
+                    section.content = text.clone();
+                    section.sources = vec![top.name.clone()];
+                    kept = true;
+                }
+            }
+            if kept {
+                pages.push(page);
+            }
+        }
+        for page in pages {
+            self.check_complete(page);
+        }
+        self.refresh_diff();
+    }
+
+    fn add_generator(&mut self) {
+        let model = self.server.current_model.clone();
+        self.generators.push(Generator {
+            model,
+            ..Default::default()
+        });
+    }
+
+    fn drop_generator(&mut self, i: usize) {
+        if i < self.generators.len() {
+            self.generators.remove(i);
+        }
+    }
+
+    fn set_generator_model(&mut self, i: usize, model: String) {
+        if let Some(generator) = self.generators.get_mut(i) {
+            generator.model = Some(model);
+        }
+    }
+
+    fn set_generator_prompt(&mut self, i: usize, prompt: Prompt) {
+        if let Some(generator) = self.generators.get_mut(i) {
+            generator.prompt = prompt;
+        }
+    }
+
+    /// finds or creates the candidate each generator writes into
+    fn generator_candidates(&mut self) -> Result<Vec<(usize, String, Prompt)>> {
+        let generators: Vec<_> = self
+            .generators
+            .iter()
+            .filter_map(|g| Some((g.name()?, g.model.clone()?, g.prompt)))
+            .collect();
+        if generators.is_empty() {
+            return Err(Error::ServerError("No candidate models selected"));
+        }
+
+        let aligned = |c: &Candidate, pages: &[Page]| {
+            c.pages.len() == pages.len()
+                && c.pages
+                    .iter()
+                    .zip(pages)
+                    .all(|((path, parts), p)| path == &p.path && parts.len() == p.sections.len())
+        };
+
+        Ok(generators
+            .into_iter()
+            .map(|(name, model, prompt)| {
+                let candidate = match self.candidates.iter().position(|c| c.name == name) {
+                    Some(i) if aligned(&self.candidates[i], &self.pages) => i,
+                    Some(i) => {
+                        self.candidates[i] = Candidate::empty(name, &self.pages);
+                        i
+                    }
+                    None => {
+                        self.candidates.push(Candidate::empty(name, &self.pages));
+                        self.candidates.len() - 1
+                    }
+                };
+                (candidate, model, prompt)
+            })
+            .collect())
+    }
+
+    /// generates the candidates for the page, runs the consensus on them and moves to the next page
+    pub fn generate(&mut self, page: usize) -> Result<Task<ConsensusAction>> {
+        self.check_ready()?;
+        if page >= self.pages.len() {
+            let file_name = self.file_name();
+            self.server.abort();
+            return Ok(Task::future(complete_dialog(file_name)).discard());
         }
+
+        let generators = self.generator_candidates()?;
+        for (candidate, ..) in &generators {
+            if let Some((_, parts)) = self.candidates[*candidate].pages.get_mut(page) {
+                parts.iter_mut().for_each(String::clear);
+            }
+        }
+
+        let current = &mut self.pages[page];
+        current.activity = Activity::Active;
+        current.clear();
+
+        let task = self
+            .server
+            .generate_candidates(&self.pages[page], &generators, page)?;
+        let generated_task = self
+            .server
+            .bind_handle(Task::done(ConsensusAction::GeneratedPage(page)));
+
+        Ok(task.chain(generated_task))
+    }
+
+    fn generated_page(&mut self, page: usize) -> Result<Task<ConsensusAction>> {
+        let model = self.check_ready()?;
+        let candidates = candidates_map(&self.candidates, page);
+        if let Some(current) = self.pages.get_mut(page) {
+            mark_missing(current, &candidates);
+        }
+        let Some(pages) = self.pages.get(..page + 1) else {
+            return Ok(Task::done(ServerAction::Abort.into()));
+        };
+
+        let task = self.server.consensus(pages, candidates, &model, page)?;
+        let complete_task = self.complete_task(page);
+        let backup_task = self.backup_task();
+        let next_task = self
+            .server
+            .bind_handle(Task::done(ConsensusAction::Generate(page + 1)));
+
+        Ok(task
+            .chain(complete_task)
+            .chain(backup_task)
+            .chain(next_task))
+    }
+
+    fn set_candidate_part(&mut self, candidate: usize, page: usize, part: usize, content: String) {
+        if let Some(part) = self
+            .candidates
+            .get_mut(candidate)
+            .and_then(|c| c.pages.get_mut(page))
+            .and_then(|(_, parts)| parts.get_mut(part))
+        {
+            *part = content;
+        }
+    }
+
+    fn backup_task(&mut self) -> Task<ConsensusAction> {
+        let backup = self.file_path.with_extension("consensus.json");
+
+        self.server
+            .bind_handle(Task::done(ConsensusAction::SaveRecovery(backup)))
+    }
+
+    fn save_json(&self, path: PathBuf) -> Result<Task<ConsensusAction>> {
+        let project = ConsensusProject {
+            pages: self.pages.clone(),
+            candidates: self.candidates.clone(),
+        };
+        let contents = serde_json::to_string_pretty(&project)?;
+        Ok(Task::future(fs::write(path, contents)).then(|e| match e {
+            Err(error) => Error::from(error).display_error(),
+            Ok(_) => Task::none(),
+        }))
+    }
+
+    fn recover(&mut self, json: &str) -> Result<()> {
+        let ConsensusProject { pages, candidates } = serde_json::from_str(json)?;
+        let mut sections: HashMap<_, _> = pages.into_iter().map(|p| (p.path, p.sections)).collect();
+
+        let mut last_section = String::new();
+        for page in self.pages.iter_mut() {
+            if let Some(current) = sections.get_mut(&page.path) {
+                mem::swap(&mut page.sections, current);
+                page.check_page(&last_section);
+            }
+            last_section = page
+                .sections
+                .last()
+                .map(|s| s.content.clone())
+                .unwrap_or_default();
+        }
+
+        self.candidates = candidates;
+        Ok(())
     }
 
     fn check_ready(&self) -> Result<String> {
@@ -132,9 +602,11 @@ impl Consensus {
 
     pub fn consensus(&mut self, page: usize) -> Result<Task<ConsensusAction>> {
         let model = self.check_ready()?;
+        let candidates = candidates_map(&self.candidates, page);
         if let Some(page) = self.pages.get_mut(page) {
             page.activity = Activity::Active;
             page.clear();
+            mark_missing(page, &candidates);
         }
 
         let Some(pages) = self.pages.get(..page + 1) else {
@@ -143,8 +615,6 @@ impl Consensus {
             return Ok(Task::future(complete_dialog(file_name.clone())).discard());
         };
 
-        let candidates = candidates_map(&self.candidates, page);
-
         let task = self.server.consensus(pages, candidates, &model, page)?;
         let complete_task = self.complete_task(page);
         let next_task = self.next_task(page);
@@ -165,17 +635,17 @@ impl Consensus {
     pub fn consensus_page(&mut self, page: usize) -> Result<Task<ConsensusAction>> {
         let model = self.check_ready()?;
 
+        let candidates = candidates_map(&self.candidates, page);
         if let Some(page) = self.pages.get_mut(page) {
             page.activity = Activity::Active;
             page.clear();
+            mark_missing(page, &candidates);
         }
 
         let Some(pages) = self.pages.get(0..page + 1) else {
             return Ok(Task::done(ServerAction::Abort.into()));
         };
 
-        let candidates = candidates_map(&self.candidates, page);
-
         let task = self.server.consensus(pages, candidates, &model, page)?;
         let complete_task = self.complete_task(page);
 
@@ -187,18 +657,27 @@ impl Consensus {
     pub fn consensus_part(&mut self, page: usize, part: usize) -> Result<Task<ConsensusAction>> {
         let model = self.check_ready()?;
 
-        if let Some(page) = self.pages.get_mut(page) {
-            page.activity = Activity::Active;
-            page.sections.get_mut(part).unwrap().content.clear();
-            page.errors.clear();
+        let candidates = candidates_map(&self.candidates, page);
+        if let Some(current) = self.pages.get_mut(page) {
+            // the other parts keep their missing errors until the page is cleared
+            current
+                .errors
+                .retain(|e| matches!(e, PageError::Missing(i) if *i != part));
+            if missing_parts(current, &candidates).contains(&part) {
+                current.mark_missing(part);
+                return Ok(Task::done(ConsensusAction::PageComplete(page)));
+            }
+            current.activity = Activity::Active;
+            let section = current.sections.get_mut(part).unwrap();
+            section.content.clear();
+            section.sources.clear();
+            section.notes.clear();
         }
 
         let Some(pages) = self.pages.get(0..page + 1) else {
             return Ok(Task::done(ServerAction::Abort.into()));
         };
 
-        let candidates = candidates_map(&self.candidates, page);
-
         let task = self
             .server
             .consensus_part(pages, candidates, model, page, part)?;
@@ -223,19 +702,39 @@ impl Consensus {
         self.server.abort();
     }
 
-    pub fn set_epub(&mut self, path: PathBuf, pages: Vec<Page>) {
+    pub fn set_epub(&mut self, path: PathBuf, pages: Vec<Page>) -> Task<ConsensusAction> {
         self.current_page = 0;
         self.file_path = path;
         self.pages = pages;
+        // tabs are realigned and checked against the new epub on the next sync
+        self.stale_tabs
+            .extend(self.candidates.iter().filter_map(|c| c.tab));
+
+        let gaps = self
+            .candidates
+            .iter_mut()
+            .filter(|c| c.tab.is_none())
+            .flat_map(|candidate| {
+                let loaded = mem::take(&mut candidate.pages);
+                let name = mem::take(&mut candidate.name);
+                let (aligned, gaps) = Candidate::aligned(name, loaded, &self.pages);
+                *candidate = aligned;
+                gaps.into_iter()
+                    .map(|gap| format!("{}: {}", candidate.name, gap))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        alignment_report(gaps)
     }
 
     fn check_complete(&mut self, page: usize) {
-        let last_section = self
-            .pages
-            .get(page - 1)
+        let last_section = page
+            .checked_sub(1)
+            .and_then(|page| self.pages.get(page))
             .and_then(|p| Some(p.sections.last()?.content.clone()))
             .unwrap_or_default();
         if let Some(page) = self.pages.get_mut(page) {
+            page.sections.iter_mut().for_each(Section::capture_notes);
             page.check_page(&last_section);
         };
     }
@@ -258,7 +757,7 @@ impl Consensus {
                 .sections
                 .iter()
                 .enumerate()
-                .map(|(i, s)| format!("{}{}\n", part_tag(i + 1), s.content))
+                .map(|(i, s)| format!("{}{}\n", part_tag(i + 1), s.noted_content()))
                 .collect();
             let contents = remove_think_tags(&text);
 
@@ -278,7 +777,7 @@ impl Consensus {
                     .sections
                     .iter()
                     .enumerate()
-                    .map(|(i, e)| format!("{}{}\n", part_tag(i + 1), e.content))
+                    .map(|(i, e)| format!("{}{}\n", part_tag(i + 1), e.noted_content()))
                     .collect();
 
                 let name = format!("{name}.md");
@@ -298,7 +797,12 @@ impl Consensus {
         };
     }
 
-    fn set_candidate(&mut self, i: Option<usize>, name: String, pages: Vec<(PathBuf, String)>) {
+    fn set_candidate(
+        &mut self,
+        i: Option<usize>,
+        name: String,
+        pages: Vec<(PathBuf, String)>,
+    ) -> Task<ConsensusAction> {
         let pages = pages
             .into_iter()
             .map(|(path, text)| {
@@ -311,11 +815,41 @@ impl Consensus {
             })
             .collect();
 
+        // without an epub there is nothing to align against yet, `set_epub` aligns it later
+        let (candidate, gaps) = match self.pages.is_empty() {
+            true => (
+                Candidate {
+                    name,
+                    pages,
+                    tab: None,
+                },
+                Vec::new(),
+            ),
+            false => Candidate::aligned(name, pages, &self.pages),
+        };
+        let gaps = gaps
+            .into_iter()
+            .map(|gap| format!("{}: {}", candidate.name, gap))
+            .collect();
+
         if let Some(i) = i {
-            *self.candidates.get_mut(i).unwrap() = Candidate { name, pages };
+            *self.candidates.get_mut(i).unwrap() = candidate;
         } else {
-            self.candidates.push(Candidate { name, pages });
+            self.candidates.push(candidate);
         };
+        alignment_report(gaps)
+    }
+
+    /// adds an open translation tab as a live candidate, its pages are filled in by `Consensus::sync_tabs`
+    fn add_tab(&mut self, tab: usize) {
+        if self.candidates.iter().all(|c| c.tab != Some(tab)) {
+            self.candidates.push(Candidate {
+                name: format!("tab {}", tab + 1),
+                tab: Some(tab),
+                ..Default::default()
+            });
+            self.stale_tabs.insert(tab);
+        }
     }
 
     fn drop_candidate(&mut self, i: usize) {
@@ -323,16 +857,45 @@ impl Consensus {
     }
 }
 
-pub fn candidates_map(candidates: &Vec<Candidate>, page: usize) -> HashMap<&OsStr, Vec<&[String]>> {
-    let candidates = candidates.iter().map(|e| &e.pages[..page + 1]).flatten();
+pub fn alignment_report(gaps: Vec<String>) -> Task<ConsensusAction> {
+    if gaps.is_empty() {
+        return Task::none();
+    }
+    Task::future(report_dialog(
+        String::from("Candidate alignment"),
+        gaps.join("\n"),
+    ))
+    .discard()
+}
+
+pub fn candidates_map(candidates: &[Candidate], page: usize) -> HashMap<&OsStr, Vec<&[String]>> {
+    let candidates = candidates
+        .iter()
+        .flat_map(|e| e.pages.iter().take(page + 1));
 
     candidates.fold(HashMap::new(), |mut acc, (p, e)| {
-        let name = p.file_stem().unwrap();
-        acc.entry(name).or_insert_with(Vec::new).push(e);
+        if let Some(name) = p.file_stem() {
+            acc.entry(name).or_insert_with(Vec::new).push(e);
+        }
         acc
     })
 }
 
+/// the parts of the page no candidate has text for
+fn missing_parts(page: &Page, candidates: &HashMap<&OsStr, Vec<&[String]>>) -> Vec<usize> {
+    let texts = page.file_stem().and_then(|stem| candidates.get(stem));
+    (0..page.sections.len())
+        .filter(|&part| texts.is_none_or(|texts| part_candidates(texts, part).is_empty()))
+        .collect()
+}
+
+/// marks the parts consensus leaves out for lack of candidate text
+fn mark_missing(page: &mut Page, candidates: &HashMap<&OsStr, Vec<&[String]>>) {
+    for part in missing_parts(page, candidates) {
+        page.mark_missing(part);
+    }
+}
+
 impl From<ServerAction> for ConsensusAction {
     fn from(action: ServerAction) -> Self {
         ConsensusAction::ServerAction(action)
@@ -1,7 +1,7 @@
 use crate::{
     controller::{
         parse::{join_partition, partition_text},
-        xml::{strip_syosetu_tags, strip_tags},
+        xml::{RubyPolicy, flatten_ruby, strip_syosetu_tags, strip_tags},
     },
     message::{Message, display_error, select_epub},
     model::doc::Doc,
@@ -19,6 +19,7 @@ pub enum DocAction {
     SetPage(usize),
     Inc,
     Dec,
+    SetRuby(RubyPolicy),
 }
 
 impl Doc {
@@ -30,6 +31,7 @@ impl Doc {
             DocAction::SetPage(page) => self.set_page(page).into(),
             DocAction::Inc => self.inc_page().into(),
             DocAction::Dec => self.dec_page().into(),
+            DocAction::SetRuby(ruby) => self.set_ruby(ruby).into(),
         }
     }
 
@@ -65,15 +67,48 @@ impl Doc {
 
         self.epub = Some(epub);
         self.set_page(0);
+        self.collect_readings();
 
         Task::none()
     }
 
+    pub fn set_ruby(&mut self, ruby: RubyPolicy) {
+        self.ruby = ruby;
+        self.collect_readings();
+        if let Some(page) = self.current_page {
+            self.set_page(page);
+        }
+    }
+
+    /// gathers the readings of the whole book when the policy collects them
+    fn collect_readings(&mut self) {
+        self.readings.clear();
+        if self.ruby != RubyPolicy::Collect {
+            return;
+        }
+        let Some(epub) = self.epub.as_mut() else {
+            return;
+        };
+
+        let current = epub.get_current_chapter();
+        for chapter in 0..epub.get_num_chapters() {
+            epub.set_current_chapter(chapter);
+            let Some((html, _)) = epub.get_current_str() else {
+                continue;
+            };
+            if let Ok((_, readings)) = flatten_ruby(&html, RubyPolicy::Collect) {
+                self.readings.extend(readings);
+            }
+        }
+        epub.set_current_chapter(current);
+    }
+
     pub fn get_page(&mut self, page: usize) -> Option<String> {
         let epub = self.epub.as_mut()?;
         epub.set_current_chapter(page);
         let html = epub.get_current_str()?.0;
         let html = strip_syosetu_tags(&html).ok()?;
+        let (html, _) = flatten_ruby(&html, self.ruby).ok()?;
         let html = strip_tags(&html).ok()?;
         let markdown = rewrite_html(&html, false);
         let lines = markdown.lines();
@@ -1,15 +1,24 @@
 use crate::{
-    actions::select_format_folder,
-    controller::builder::DocBuilder,
+    actions::{load_file, select_format_folder, server_action::ServerAction},
+    controller::{
+        builder::{BuilderPage, DocBuilder},
+        get_ordered_path,
+        parse::first_heading,
+        prompts::DESCRIPTION_PROMPT,
+        resource::normalize_path,
+        style::{ParagraphStyle, StyleTheme},
+        typography::{EllipsisStyle, QuoteStyle},
+        validate::validate_epub,
+    },
     error::{Error, Result},
     message::{Message, select_epub},
-    model::format::{Format, FormatPage},
+    model::format::{BuiltEpub, EpubMetadata, Format, FormatPage, PageMapping, TocEntry},
 };
 use epub::doc::EpubDoc;
 use iced::{Task, widget::image::Handle};
 use std::{
+    collections::{HashMap, HashSet},
     io::Cursor,
-    mem,
     path::{Path, PathBuf},
 };
 
@@ -28,7 +37,40 @@ pub enum FormatAction {
     },
     SetTitle(String),
     SetAuthors(String),
+    SetPublisher(String),
+    SetDescription(String),
+    SetSeries(String),
+    SetVolume(String),
+    SetIdentifiers(String),
+    SetSubjects(String),
+    SetDate(String),
+    SetLanguage(String),
+    TranslateDescription,
+    ServerAction(ServerAction),
+    SetTocTitle(usize, String),
+    SetMapping(usize, Option<usize>),
+    SetTheme(StyleTheme),
+    LoadCss,
+    SetCustomCss(String),
+    SetFontFamily(String),
+    SetParagraphs(ParagraphStyle),
+    SetSceneBreak(String),
+    SetJustify(bool),
+    SetKeepSourceStyles(bool),
+    SetQuotes(QuoteStyle),
+    SetNestedSingle(bool),
+    SetEllipsis(EllipsisStyle),
+    SetDashes(bool),
+    SetFullWidth(bool),
+    SetWaveDash(bool),
+    SetBrackets(bool),
+    PreviewTypography,
+    ClosePreview,
     Build,
+    SetBuilt(BuiltEpub),
+    SaveBuilt,
+    Saved,
+    DiscardBuilt,
 }
 
 impl Format {
@@ -36,6 +78,41 @@ impl Format {
         match action {
             FormatAction::SetTitle(title) => self.set_title(title).into(),
             FormatAction::SetAuthors(authors) => self.set_authors(authors).into(),
+            FormatAction::SetPublisher(publisher) => self.set_publisher(publisher).into(),
+            FormatAction::SetDescription(description) => self.set_description(description).into(),
+            FormatAction::SetSeries(series) => self.set_series(series).into(),
+            FormatAction::SetVolume(volume) => self.set_volume(volume).into(),
+            FormatAction::SetIdentifiers(identifiers) => self.set_identifiers(identifiers).into(),
+            FormatAction::SetSubjects(subjects) => self.set_subjects(subjects).into(),
+            FormatAction::SetDate(date) => self.set_date(date).into(),
+            FormatAction::SetLanguage(language) => self.set_language(language).into(),
+            FormatAction::TranslateDescription => self
+                .translate_description()
+                .unwrap_or_else(Error::display_error),
+            FormatAction::ServerAction(action) => self
+                .server
+                .perform(action)
+                .map(|action| FormatAction::from(action).into()),
+            FormatAction::SetTocTitle(i, title) => self.set_toc_title(i, title).into(),
+            FormatAction::SetMapping(item, page) => self.set_mapping(item, page).into(),
+            FormatAction::SetTheme(theme) => self.set_theme(theme).into(),
+            FormatAction::LoadCss => Task::future(load_file("stylesheet", "css", &["css"]))
+                .and_then(|css| Task::done(FormatAction::SetCustomCss(css).into())),
+            FormatAction::SetCustomCss(css) => self.set_custom_css(css).into(),
+            FormatAction::SetFontFamily(font) => self.set_font_family(font).into(),
+            FormatAction::SetParagraphs(style) => self.set_paragraphs(style).into(),
+            FormatAction::SetSceneBreak(glyph) => self.set_scene_break(glyph).into(),
+            FormatAction::SetJustify(justify) => self.set_justify(justify).into(),
+            FormatAction::SetKeepSourceStyles(keep) => self.set_keep_source_styles(keep).into(),
+            FormatAction::SetQuotes(quotes) => self.set_quotes(quotes).into(),
+            FormatAction::SetNestedSingle(single) => self.set_nested_single(single).into(),
+            FormatAction::SetEllipsis(ellipsis) => self.set_ellipsis(ellipsis).into(),
+            FormatAction::SetDashes(dashes) => self.set_dashes(dashes).into(),
+            FormatAction::SetFullWidth(full_width) => self.set_full_width(full_width).into(),
+            FormatAction::SetWaveDash(wave_dash) => self.set_wave_dash(wave_dash).into(),
+            FormatAction::SetBrackets(brackets) => self.set_brackets(brackets).into(),
+            FormatAction::PreviewTypography => self.preview_typography().into(),
+            FormatAction::ClosePreview => self.close_preview().into(),
             FormatAction::SetPages { name, pages } => self.set_pages(name, pages).into(),
             FormatAction::SelectEpub => Task::future(select_epub()).and_then(|(path, buffer)| {
                 Task::done(FormatAction::SetEpub { path, buffer }.into())
@@ -50,17 +127,212 @@ impl Format {
             },
             FormatAction::Build => Task::done(self.get_build_content())
                 .and_then(|builder| Task::done(builder.build()))
-                .and_then(|(content, name)| Task::future(save_epub(content, name)))
-                .then(|e| match e {
-                    Ok(_) => Task::none(),
+                .then(|built| match built {
+                    Ok((content, name)) => {
+                        let issues = validate_epub(&content);
+                        let built = BuiltEpub {
+                            content,
+                            name,
+                            issues,
+                        };
+                        Task::done(FormatAction::SetBuilt(built).into())
+                    }
                     Err(error) => error.display_error(),
                 }),
+            FormatAction::SetBuilt(built) => self.set_built(built).into(),
+            FormatAction::SaveBuilt => match &self.built {
+                Some(BuiltEpub { content, name, .. }) => {
+                    Task::future(save_epub(content.clone(), name.clone())).then(|e| match e {
+                        Ok(true) => Task::done(FormatAction::Saved.into()),
+                        Ok(false) => Task::none(),
+                        Err(error) => error.display_error(),
+                    })
+                }
+                None => Task::none(),
+            },
+            FormatAction::Saved => self.saved().into(),
+            FormatAction::DiscardBuilt => self.discard_built().into(),
         }
     }
 
+    fn set_theme(&mut self, theme: StyleTheme) {
+        self.style.theme = theme;
+    }
+
+    fn set_custom_css(&mut self, css: String) {
+        self.style.custom_css = Some(css);
+        self.style.theme = StyleTheme::Custom;
+    }
+
+    fn set_font_family(&mut self, font: String) {
+        self.style.font_family = font;
+    }
+
+    fn set_paragraphs(&mut self, style: ParagraphStyle) {
+        self.style.paragraphs = style;
+    }
+
+    fn set_scene_break(&mut self, glyph: String) {
+        self.style.scene_break = glyph;
+    }
+
+    fn set_justify(&mut self, justify: bool) {
+        self.style.justify = justify;
+    }
+
+    fn set_keep_source_styles(&mut self, keep: bool) {
+        self.style.keep_source_styles = keep;
+    }
+
+    fn set_quotes(&mut self, quotes: QuoteStyle) {
+        self.style.typography.quotes = quotes;
+    }
+
+    fn set_nested_single(&mut self, single: bool) {
+        self.style.typography.nested_single = single;
+    }
+
+    fn set_ellipsis(&mut self, ellipsis: EllipsisStyle) {
+        self.style.typography.ellipsis = ellipsis;
+    }
+
+    fn set_dashes(&mut self, dashes: bool) {
+        self.style.typography.dashes = dashes;
+    }
+
+    fn set_full_width(&mut self, full_width: bool) {
+        self.style.typography.full_width = full_width;
+    }
+
+    fn set_wave_dash(&mut self, wave_dash: bool) {
+        self.style.typography.wave_dash = wave_dash;
+    }
+
+    fn set_brackets(&mut self, brackets: bool) {
+        self.style.typography.brackets = brackets;
+    }
+
+    /// lists the changes the typography pass makes to every mapped page, in reading order
+    fn preview_typography(&mut self) {
+        let typography = &self.style.typography;
+        let preview: String = self
+            .mapping
+            .iter()
+            .filter_map(|m| self.pages.get(m.page?))
+            .filter_map(|page| {
+                let changes = typography.changes(&page.content);
+                if changes.is_empty() {
+                    return None;
+                }
+                let changes: String = changes
+                    .into_iter()
+                    .map(|(before, after)| format!("- {before}\n+ {after}\n"))
+                    .collect();
+                Some(format!("{}\n{changes}\n", page.file_name()))
+            })
+            .collect();
+
+        self.typography_preview = match preview.is_empty() {
+            true => Some("no typography changes".to_string()),
+            false => Some(preview),
+        };
+    }
+
+    fn close_preview(&mut self) {
+        self.typography_preview = None;
+    }
+
+    fn set_built(&mut self, built: BuiltEpub) {
+        self.built = Some(built);
+    }
+
+    fn discard_built(&mut self) {
+        self.built = None;
+    }
+
+    /// the epub is written, so the book is cleared for the next one, the style is kept
+    fn saved(&mut self) {
+        self.built = None;
+        self.epub = None;
+        self.epub_path = PathBuf::new();
+        self.pages.clear();
+        self.mapping.clear();
+        self.metadata = EpubMetadata::default();
+        self.toc.clear();
+        self.source_folder.clear();
+        self.cover = None;
+        self.typography_preview = None;
+    }
+
     fn set_pages(&mut self, name: String, pages: Vec<(PathBuf, String)>) {
         self.pages = pages.into_iter().map(|e| FormatPage::from(e)).collect();
+        self.pages.sort_by(|a, b| a.path.cmp(&b.path));
         self.source_folder = name;
+        self.map_pages();
+    }
+
+    /// maps every spine item to the translated page named after its stem, a page is only used once
+    fn map_pages(&mut self) {
+        let Some(epub) = self.epub.as_ref() else {
+            self.mapping.clear();
+            return;
+        };
+
+        let mut pages: HashMap<_, _> = self
+            .pages
+            .iter()
+            .enumerate()
+            .filter_map(|(i, p)| Some((p.path.file_stem()?.to_os_string(), i)))
+            .collect();
+
+        self.mapping = get_ordered_path(epub)
+            .into_iter()
+            .map(|source| PageMapping {
+                page: source.file_stem().and_then(|stem| pages.remove(stem)),
+                source: normalize_path(source),
+            })
+            .collect();
+        self.fill_headings();
+    }
+
+    /// maps the spine item to the page, taking the page from any other spine item
+    fn set_mapping(&mut self, item: usize, page: Option<usize>) {
+        if page.is_some() {
+            self.mapping
+                .iter_mut()
+                .filter(|m| m.page == page)
+                .for_each(|m| m.page = None);
+        }
+        if let Some(mapping) = self.mapping.get_mut(item) {
+            mapping.page = page;
+        }
+        self.fill_headings();
+    }
+
+    /// titles the first toc entry of every page with the first heading of its translation
+    fn fill_headings(&mut self) {
+        let headings: HashMap<_, _> = self
+            .mapping
+            .iter()
+            .filter_map(|m| {
+                let page = self.pages.get(m.page?)?;
+                Some((&m.source, first_heading(&page.content)?))
+            })
+            .collect();
+
+        let mut seen = HashSet::new();
+        for entry in self.toc.iter_mut() {
+            entry.heading = seen
+                .insert(entry.path.clone())
+                .then(|| headings.get(&entry.path).cloned())
+                .flatten();
+        }
+    }
+
+    fn set_toc_title(&mut self, i: usize, title: String) {
+        if let Some(entry) = self.toc.get_mut(i) {
+            entry.title = Some(title);
+        }
     }
 
     fn set_title(&mut self, title: String) {
@@ -71,47 +343,122 @@ impl Format {
         self.metadata.authors = authors
     }
 
+    fn set_publisher(&mut self, publisher: String) {
+        self.metadata.publisher = publisher
+    }
+
+    fn set_series(&mut self, series: String) {
+        self.metadata.series = series
+    }
+
+    fn set_volume(&mut self, volume: String) {
+        self.metadata.volume = volume
+    }
+
+    fn set_identifiers(&mut self, identifiers: String) {
+        self.metadata.identifiers = identifiers
+    }
+
+    fn set_subjects(&mut self, subjects: String) {
+        self.metadata.subjects = subjects
+    }
+
+    fn set_date(&mut self, date: String) {
+        self.metadata.date = date
+    }
+
+    fn set_language(&mut self, language: String) {
+        self.metadata.language = language
+    }
+
+    fn set_description(&mut self, description: String) {
+        self.metadata.description = description
+    }
+
+    /// replaces the description with its translation by the selected model
+    fn translate_description(&mut self) -> Result<Task<Message>> {
+        if !self.server.connected() {
+            return Err(Error::ServerError("Not connected to a server"));
+        }
+        let model = self
+            .server
+            .current_model
+            .clone()
+            .ok_or(Error::ServerError("No model selected"))?;
+
+        let task = self.server.client.complete(
+            DESCRIPTION_PROMPT,
+            self.metadata.description.clone(),
+            &model,
+            self.server.settings.think,
+        )?;
+        let (task, handle) = task.abortable();
+        self.server.handles.push(handle.abort_on_drop());
+
+        Ok(task
+            .then(|description| match description {
+                Ok(description) => Task::done(FormatAction::SetDescription(description).into()),
+                Err(error) => error.display_error(),
+            })
+            .chain(Task::done(FormatAction::from(ServerAction::Abort).into())))
+    }
+
     fn set_epub(&mut self, path: PathBuf, buffer: Vec<u8>) -> Result<()> {
         let mut epub = EpubDoc::from_reader(Cursor::new(buffer))?;
         let cover = epub.get_cover().map(|e| Handle::from_bytes(e.0));
-        let authors = epub
-            .metadata
-            .iter()
-            .find(|e| e.property == "creator")
-            .map(|e| e.value.to_owned())
-            .unwrap_or_default();
-
         let title = path
             .file_stem()
             .map(|e| e.to_string_lossy().to_string())
             .unwrap_or_default();
 
+        self.metadata = EpubMetadata::from_source(&epub.metadata);
         self.metadata.title = title;
-        self.metadata.authors = authors;
+        self.toc = TocEntry::flatten(&epub.toc, 1);
         self.epub_path = path;
         self.epub = Some(epub);
         self.cover = cover;
+        self.map_pages();
 
         Ok(())
     }
 
-    pub fn get_build_content(&mut self) -> Result<DocBuilder> {
-        let epub = mem::take(&mut self.epub).ok_or(Error::BuildError("Epub not found"))?;
-        let pages = mem::take(&mut self.pages);
-        let name = mem::take(&mut self.epub_path)
+    /// the builder works on a copy so the book, its mapping and its metadata survive a
+    /// discarded build
+    pub fn get_build_content(&self) -> Result<DocBuilder> {
+        let epub = self
+            .epub
+            .clone()
+            .ok_or(Error::BuildError("Epub not found"))?;
+        let pages = self
+            .mapping
+            .iter()
+            .filter_map(|m| {
+                let page = self.pages.get(m.page?)?;
+                Some(BuilderPage {
+                    path: m.source.clone(),
+                    content: page.content.clone(),
+                })
+            })
+            .collect();
+        let name = self
+            .epub_path
             .file_name()
             .map(|e| e.to_string_lossy().to_string())
             .unwrap_or_default();
-        let metadata = mem::take(&mut self.metadata);
 
-        self.source_folder.clear();
-        self.cover = None;
-
-        DocBuilder::new(epub, name, pages, metadata)
+        DocBuilder::new(
+            epub,
+            name,
+            pages,
+            self.metadata.clone(),
+            self.toc.clone(),
+            self.style.clone(),
+        )
     }
 }
 
-pub async fn save_epub<T: Into<String>>(content: Vec<u8>, file_name: T) -> Result<()> {
+/// whether the epub was written, the dialog may be cancelled
+pub async fn save_epub<T: Into<String>>(content: Vec<u8>, file_name: T) -> Result<bool> {
     let handle = rfd::AsyncFileDialog::new()
         .set_title("save epub")
         .set_file_name(file_name)
@@ -119,8 +466,17 @@ pub async fn save_epub<T: Into<String>>(content: Vec<u8>, file_name: T) -> Resul
         .save_file()
         .await;
 
-    if let Some(handle) = handle {
-        handle.write(&content).await?;
+    match handle {
+        Some(handle) => {
+            handle.write(&content).await?;
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+impl From<ServerAction> for FormatAction {
+    fn from(action: ServerAction) -> Self {
+        FormatAction::ServerAction(action)
     }
-    Ok(())
 }
@@ -2,7 +2,8 @@ use crate::{
     controller::{
         get_ordered_path,
         parse::partition_text,
-        xml::{strip_syosetu_tags, strip_tags},
+        structure::{TextMode, extract_units},
+        xml::{RubyPolicy, flatten_ruby, strip_syosetu_tags, strip_tags},
     },
     error::{Error, Result},
     model::page::Page,
@@ -75,7 +76,22 @@ pub async fn load_recovery() -> Option<Vec<Page>> {
     Some(pages)
 }
 
-pub async fn get_pages(file_path: PathBuf, buffer: Vec<u8>) -> Result<(PathBuf, Vec<Page>)> {
+pub async fn load_file(title: &str, filter: &str, extensions: &[&str]) -> Option<String> {
+    let handle = rfd::AsyncFileDialog::new()
+        .add_filter(filter, extensions)
+        .set_title(title)
+        .pick_file()
+        .await?;
+    let buffer = handle.read().await;
+    String::from_utf8(buffer).ok()
+}
+
+pub async fn get_pages(
+    file_path: PathBuf,
+    buffer: Vec<u8>,
+    ruby: RubyPolicy,
+    mode: TextMode,
+) -> Result<(PathBuf, Vec<Page>)> {
     let mut epub = EpubDoc::from_reader(Cursor::new(buffer))?;
     let paths = get_ordered_path(&epub);
 
@@ -86,6 +102,10 @@ pub async fn get_pages(file_path: PathBuf, buffer: Vec<u8>) -> Result<(PathBuf,
                 .get_resource_str_by_path(&path)
                 .ok_or(Error::Error(format!("Invalid file in epub: {:#?}", path)))?;
             let html = strip_syosetu_tags(&html)?;
+            if mode == TextMode::Structure {
+                return Ok((path, extract_units(&html, ruby)?.join("\n")));
+            }
+            let (html, _) = flatten_ruby(&html, ruby)?;
             let html = strip_tags(&html)?;
             let markdown = rewrite_html(&html, false);
             let markdown: Vec<_> = markdown.lines().map(|s| s.trim()).collect();
@@ -125,6 +145,16 @@ pub async fn complete_dialog(file_name: String) {
         .await;
 }
 
+pub async fn report_dialog(title: String, description: String) {
+    rfd::AsyncMessageDialog::new()
+        .set_level(rfd::MessageLevel::Warning)
+        .set_title(title)
+        .set_description(description)
+        .set_buttons(rfd::MessageButtons::Ok)
+        .show()
+        .await;
+}
+
 pub async fn select_format_folder(dir: PathBuf) -> Option<(String, Vec<(PathBuf, String)>)> {
     let handle = rfd::AsyncFileDialog::new()
         .set_title("select translated folder")
@@ -1,16 +1,34 @@
 use crate::{
     actions::{
-        clean_invisible_chars, complete_dialog, get_pages, load_recovery, pick_save_folder,
-        save_file, server_action::ServerAction,
+        clean_invisible_chars, complete_dialog, get_pages, load_file, load_recovery,
+        pick_save_folder, report_dialog, save_file, server_action::ServerAction,
+    },
+    controller::{
+        Segment,
+        fidelity::chrf,
+        parse::remove_think_tags,
+        part_tag,
+        spreadsheet::{from_csv, to_csv},
+        structure::TextMode,
+        tmx::{from_tmx, lookup_memory, to_tmx},
+        xliff::{from_xliff, to_xliff},
+        xml::RubyPolicy,
     },
-    controller::{parse::remove_think_tags, part_tag},
     error::{Error, Result},
     message::{display_error, select_epub},
-    model::{Activity, page::Page, translation::Translation},
+    model::{
+        Activity,
+        page::{BackTranslation, Comment, Note, Page, Section},
+        translation::Translation,
+    },
     view::DisplayType,
 };
 use iced::Task;
-use std::{collections::HashMap, mem, path::PathBuf};
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+    path::{Path, PathBuf},
+};
 use tokio::fs;
 
 #[non_exhaustive]
@@ -50,6 +68,48 @@ pub enum TransAction {
     SaveTranslation(String),
     ServerAction(ServerAction),
     SetDisplay(DisplayType),
+    SetCommentPart(usize),
+    SetCommentRange(String),
+    SetCommentText(String),
+    AddComment(usize),
+    ResolveComment {
+        page: usize,
+        part: usize,
+        comment: usize,
+    },
+    SetNotePart(usize),
+    SetNoteOffset(String),
+    SetNoteText(String),
+    AddNote(usize),
+    RemoveNote {
+        page: usize,
+        part: usize,
+        note: usize,
+    },
+    ExportQa(String),
+    ExportXliff(String),
+    ImportXliff,
+    SetXliff(String),
+    ExportTmx(String),
+    ImportTmx,
+    SetTmx(String),
+    ExportCsv(String),
+    ImportCsv,
+    SetCsv(String),
+    UseSuggestion {
+        page: usize,
+        part: usize,
+        suggestion: usize,
+    },
+    SetRuby(RubyPolicy),
+    SetTextMode(TextMode),
+    SetBackModel(String),
+    BackTranslate(usize),
+    SetBackTranslation {
+        page: usize,
+        part: usize,
+        text: String,
+    },
 }
 
 impl Translation {
@@ -80,12 +140,19 @@ impl Translation {
             TransAction::SaveRecovery(path) => {
                 self.save_json(path).unwrap_or_else(Error::display_error)
             }
-            TransAction::OpenEpub => Task::future(select_epub())
-                .and_then(|(name, buffer)| Task::future(get_pages(name, buffer)))
-                .then(|doc| match doc {
-                    Ok((name, pages)) => Task::done(TransAction::SetEpub { name, pages }),
-                    Err(error) => error.display_error(),
-                }),
+            TransAction::OpenEpub => {
+                let (ruby, mode) = (self.ruby, self.text_mode);
+                Task::future(select_epub())
+                    .and_then(move |(name, buffer)| {
+                        Task::future(get_pages(name, buffer, ruby, mode))
+                    })
+                    .then(|doc| match doc {
+                        Ok((name, pages)) => Task::done(TransAction::SetEpub { name, pages }),
+                        Err(error) => error.display_error(),
+                    })
+            }
+            TransAction::SetRuby(ruby) => self.set_ruby(ruby).into(),
+            TransAction::SetTextMode(mode) => self.set_text_mode(mode).into(),
             TransAction::SaveTranslation(file_name) => Task::future(pick_save_folder(file_name))
                 .and_then(|path| Task::future(async { fs::create_dir(&path).await.map(|_| path) }))
                 .then(|path| match path {
@@ -99,9 +166,371 @@ impl Translation {
             TransAction::Recover => Task::future(load_recovery())
                 .and_then(|pages| Task::done(TransAction::RecoverPages(pages))),
             TransAction::SetDisplay(display) => self.set_display(display).into(),
+            TransAction::SetCommentPart(part) => self.set_comment_part(part).into(),
+            TransAction::SetCommentRange(range) => self.set_comment_range(range).into(),
+            TransAction::SetCommentText(text) => self.set_comment_text(text).into(),
+            TransAction::AddComment(page) => match self.add_comment(page) {
+                Ok(_) => Task::none(),
+                Err(error) => error.display_error(),
+            },
+            TransAction::ResolveComment {
+                page,
+                part,
+                comment,
+            } => self.resolve_comment(page, part, comment).into(),
+            TransAction::SetNotePart(part) => self.set_note_part(part).into(),
+            TransAction::SetNoteOffset(offset) => self.set_note_offset(offset).into(),
+            TransAction::SetNoteText(text) => self.set_note_text(text).into(),
+            TransAction::AddNote(page) => match self.add_note(page) {
+                Ok(_) => Task::none(),
+                Err(error) => error.display_error(),
+            },
+            TransAction::RemoveNote { page, part, note } => {
+                self.remove_note(page, part, note).into()
+            }
+            TransAction::ExportQa(file_name) => self
+                .export_qa(file_name)
+                .unwrap_or_else(Error::display_error),
+            TransAction::ExportXliff(file_name) => self
+                .export_xliff(file_name)
+                .unwrap_or_else(Error::display_error),
+            TransAction::ImportXliff => {
+                Task::future(load_file("xliff", "xliff", &["xlf", "xliff"]))
+                    .and_then(|xliff| Task::done(TransAction::SetXliff(xliff)))
+            }
+            TransAction::SetXliff(xliff) => match from_xliff(&xliff) {
+                Ok(segments) => self.import_segments(segments),
+                Err(error) => error.display_error(),
+            },
+            TransAction::ExportTmx(file_name) => self
+                .export_tmx(file_name)
+                .unwrap_or_else(Error::display_error),
+            TransAction::ImportTmx => Task::future(load_file("tmx", "tmx", &["tmx"]))
+                .and_then(|tmx| Task::done(TransAction::SetTmx(tmx))),
+            TransAction::SetTmx(tmx) => match from_tmx(&tmx) {
+                Ok(memory) => self.import_memory(memory),
+                Err(error) => error.display_error(),
+            },
+            TransAction::ExportCsv(file_name) => self.export_csv(file_name),
+            TransAction::ImportCsv => Task::future(load_file("csv", "csv", &["csv"]))
+                .and_then(|csv| Task::done(TransAction::SetCsv(csv))),
+            TransAction::SetCsv(csv) => match from_csv(&csv) {
+                Ok(segments) => self.import_segments(segments),
+                Err(error) => error.display_error(),
+            },
+            TransAction::UseSuggestion {
+                page,
+                part,
+                suggestion,
+            } => self.use_suggestion(page, part, suggestion).into(),
+            TransAction::SetBackModel(model) => self.set_back_model(model).into(),
+            TransAction::BackTranslate(page) => self
+                .back_translate(page)
+                .unwrap_or_else(Error::display_error),
+            TransAction::SetBackTranslation { page, part, text } => {
+                self.set_back_translation(page, part, text).into()
+            }
+        }
+    }
+
+    fn set_ruby(&mut self, ruby: RubyPolicy) {
+        self.ruby = ruby;
+    }
+
+    fn set_text_mode(&mut self, mode: TextMode) {
+        self.text_mode = mode;
+    }
+
+    fn set_back_model(&mut self, model: String) {
+        self.back_model = Some(model);
+    }
+
+    pub fn back_translate(&mut self, page: usize) -> Result<Task<TransAction>> {
+        if !self.server.connected() {
+            return Err(Error::ServerError("Not connected to a server"));
+        }
+        let model = self
+            .back_model
+            .clone()
+            .ok_or(Error::ServerError("No back-translation model selected"))?;
+        let Some(current) = self.pages.get_mut(page) else {
+            return Ok(Task::none());
+        };
+        current.activity = Activity::Active;
+        current
+            .sections
+            .iter_mut()
+            .for_each(|s| s.back_translation = None);
+
+        let task = self
+            .server
+            .back_translate(&self.pages[page], &model, page)?;
+        let complete_task = self.complete_task(page);
+        let backup_task = self.backup_task();
+
+        Ok(task
+            .chain(complete_task)
+            .chain(backup_task)
+            .chain(Task::done(ServerAction::Abort.into())))
+    }
+
+    fn set_back_translation(&mut self, page: usize, part: usize, text: String) {
+        if let Some(section) = self
+            .pages
+            .get_mut(page)
+            .and_then(|p| p.sections.get_mut(part))
+        {
+            let score = chrf(&text, &section.japanese);
+            section.back_translation = Some(BackTranslation { text, score });
+        }
+    }
+
+    fn set_comment_part(&mut self, part: usize) {
+        self.comment.part = part;
+    }
+
+    fn set_comment_range(&mut self, range: String) {
+        self.comment.range = range;
+    }
+
+    fn set_comment_text(&mut self, text: String) {
+        self.comment.text = text;
+    }
+
+    fn add_comment(&mut self, page: usize) -> Result<()> {
+        let range = self.comment.range().ok_or(Error::Error(format!(
+            "Invalid comment range: {}",
+            self.comment.range
+        )))?;
+        let part = self.comment.part.saturating_sub(1);
+        let section = self
+            .pages
+            .get_mut(page)
+            .and_then(|p| p.sections.get_mut(part))
+            .ok_or(Error::Error(format!("Invalid part: {}", part + 1)))?;
+
+        let text = mem::take(&mut self.comment.text);
+        section.comments.push(Comment::new(text, range));
+        self.comment.range.clear();
+        Ok(())
+    }
+
+    fn resolve_comment(&mut self, page: usize, part: usize, comment: usize) {
+        if let Some(comment) = self
+            .pages
+            .get_mut(page)
+            .and_then(|p| p.sections.get_mut(part))
+            .and_then(|s| s.comments.get_mut(comment))
+        {
+            comment.resolved = true;
         }
     }
 
+    fn set_note_part(&mut self, part: usize) {
+        self.note.part = part;
+    }
+
+    fn set_note_offset(&mut self, offset: String) {
+        self.note.offset = offset;
+    }
+
+    fn set_note_text(&mut self, text: String) {
+        self.note.text = text;
+    }
+
+    fn add_note(&mut self, page: usize) -> Result<()> {
+        let offset = self.note.offset().ok_or(Error::Error(format!(
+            "Invalid note offset: {}",
+            self.note.offset
+        )))?;
+        let part = self.note.part.saturating_sub(1);
+        let section = self
+            .pages
+            .get_mut(page)
+            .and_then(|p| p.sections.get_mut(part))
+            .ok_or(Error::Error(format!("Invalid part: {}", part + 1)))?;
+
+        let text = mem::take(&mut self.note.text);
+        section.notes.push(Note::new(text, offset));
+        self.note.offset.clear();
+        Ok(())
+    }
+
+    fn remove_note(&mut self, page: usize, part: usize, note: usize) {
+        if let Some(section) = self
+            .pages
+            .get_mut(page)
+            .and_then(|p| p.sections.get_mut(part))
+            && note < section.notes.len()
+        {
+            section.notes.remove(note);
+        }
+    }
+
+    fn export_xliff(&self, file_name: String) -> Result<Task<TransAction>> {
+        let contents = to_xliff(&self.pages)?;
+        let stem = Path::new(&file_name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let name = format!("{stem}.xlf");
+
+        Ok(Task::future(save_file(name, contents)).then(|e| match e {
+            Err(error) => error.display_error(),
+            Ok(_) => Task::none(),
+        }))
+    }
+
+    /// writes the targets of matching segments into their sections, segments whose source no
+    /// longer matches the section are skipped and reported, empty targets keep the translation
+    pub fn import_segments(&mut self, segments: Vec<Segment>) -> Task<TransAction> {
+        let mut mismatches = Vec::new();
+        let mut changed = HashSet::new();
+
+        for segment in segments.into_iter().filter(|s| !s.target.trim().is_empty()) {
+            let label = format!(
+                "{} part {}",
+                segment
+                    .path
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                segment.part + 1
+            );
+            let Some((i, page)) = self
+                .pages
+                .iter_mut()
+                .enumerate()
+                .find(|(_, p)| p.path == segment.path)
+            else {
+                mismatches.push(format!("{label}: page not found"));
+                continue;
+            };
+            let Some(section) = page.sections.get_mut(segment.part) else {
+                mismatches.push(format!("{label}: part not found"));
+                continue;
+            };
+            if section.japanese.trim() != segment.source.trim() {
+                mismatches.push(format!("{label}: source does not match"));
+                continue;
+            }
+            section.content = segment.target;
+            changed.insert(i);
+        }
+
+        let mut changed: Vec<_> = changed.into_iter().collect();
+        changed.sort();
+        for page in changed {
+            self.check_complete(page);
+        }
+
+        if mismatches.is_empty() {
+            return Task::none();
+        }
+        let description = mismatches.join("\n");
+        Task::future(report_dialog(
+            String::from("Import mismatches"),
+            description,
+        ))
+        .discard()
+    }
+
+    fn export_tmx(&self, file_name: String) -> Result<Task<TransAction>> {
+        let contents = to_tmx(&self.pages)?;
+        let stem = Path::new(&file_name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let name = format!("{stem}.tmx");
+
+        Ok(Task::future(save_file(name, contents)).then(|e| match e {
+            Err(error) => error.display_error(),
+            Ok(_) => Task::none(),
+        }))
+    }
+
+    fn export_csv(&self, file_name: String) -> Task<TransAction> {
+        let contents = to_csv(&self.pages);
+        let stem = Path::new(&file_name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let name = format!("{stem}.csv");
+
+        Task::future(save_file(name, contents)).then(|e| match e {
+            Err(error) => error.display_error(),
+            Ok(_) => Task::none(),
+        })
+    }
+
+    /// seeds untranslated sections from the memory, translated sections keep their content and
+    /// receive the memory entry as a suggestion
+    fn import_memory(&mut self, memory: HashMap<String, String>) -> Task<TransAction> {
+        let mut seeded = 0;
+        let mut suggested = 0;
+        let mut changed = Vec::new();
+
+        for (i, page) in self.pages.iter_mut().enumerate() {
+            let mut page_changed = false;
+            for section in page.sections.iter_mut() {
+                let Some(target) = lookup_memory(&memory, &section.japanese) else {
+                    continue;
+                };
+                if section.content.trim().is_empty() {
+                    section.content = target;
+                    seeded += 1;
+                    page_changed = true;
+                } else if section.content.trim() != target && !section.suggestions.contains(&target)
+                {
+                    section.suggestions.push(target);
+                    suggested += 1;
+                }
+            }
+            if page_changed {
+                changed.push(i);
+            }
+        }
+
+        for page in changed {
+            self.check_complete(page);
+        }
+
+        let description = format!("seeded {seeded} part(s)\nsuggested {suggested} part(s)");
+        Task::future(report_dialog(
+            String::from("Translation memory"),
+            description,
+        ))
+        .discard()
+    }
+
+    fn use_suggestion(&mut self, page: usize, part: usize, suggestion: usize) {
+        if let Some(section) = self
+            .pages
+            .get_mut(page)
+            .and_then(|p| p.sections.get_mut(part))
+            && suggestion < section.suggestions.len()
+        {
+            section.content = section.suggestions.remove(suggestion);
+            section.suggestions.clear();
+            self.check_complete(page);
+        }
+    }
+
+    fn export_qa(&self, file_name: String) -> Result<Task<TransAction>> {
+        let report: Vec<_> = self.pages.iter().map(Page::qa_report).collect();
+        let contents = serde_json::to_string_pretty(&report)?;
+        let stem = Path::new(&file_name)
+            .file_stem()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let name = format!("{stem}_qa.json");
+
+        Ok(Task::future(save_file(name, contents)).then(|e| match e {
+            Err(error) => error.display_error(),
+            Ok(_) => Task::none(),
+        }))
+    }
+
     fn set_display(&mut self, display: DisplayType) {
         self.display = display;
     }
@@ -165,6 +594,7 @@ impl Translation {
             .unwrap_or_default();
 
         if let Some(page) = self.pages.get_mut(page) {
+            page.sections.iter_mut().for_each(Section::capture_notes);
             page.check_page(&last_section);
         };
     }
@@ -176,7 +606,7 @@ impl Translation {
                     .sections
                     .iter()
                     .enumerate()
-                    .map(|(i, e)| format!("{}{}\n", part_tag(i + 1), e.content))
+                    .map(|(i, e)| format!("{}{}\n", part_tag(i + 1), e.noted_content()))
                     .collect();
 
                 Task::future(save_file(format!("{name}.md"), content)).discard()
@@ -195,7 +625,7 @@ impl Translation {
                 .sections
                 .iter()
                 .enumerate()
-                .map(|(i, s)| format!("{}{}\n", part_tag(i + 1), s.content))
+                .map(|(i, s)| format!("{}{}\n", part_tag(i + 1), s.noted_content()))
                 .collect();
             let contents = remove_think_tags(&text);
 
@@ -297,7 +727,10 @@ impl Translation {
 
         let current = pages.last_mut().unwrap();
         current.activity = Activity::Active;
-        current.sections.get_mut(part).unwrap().content.clear();
+        let section = current.sections.get_mut(part).unwrap();
+        section.content.clear();
+        section.back_translation = None;
+        section.notes.clear();
         current.errors.clear();
 
         let task = self.server.translate_part(pages, &model, page, part)?;
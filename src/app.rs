@@ -55,7 +55,7 @@ impl Translator {
             View::Doc => doc_view(&self.doc).map(Into::into),
             View::Translation => translation_view(&self.translations, self.active_tab),
             View::Format => format_view(&self.format).map(Into::into),
-            View::Consensus => consensus_view(&self.consensus).map(Into::into),
+            View::Consensus => consensus_view(&self.consensus, &self.translations).map(Into::into),
         }
     }
 }
@@ -0,0 +1,45 @@
+use crate::controller::diff::sentence_ranges;
+
+/// splits the candidate text into one part per source section, cutting at the sentence
+/// boundary closest to where each section ends in proportion to the source length
+pub fn realign_parts(parts: &[String], sources: &[&str]) -> Vec<String> {
+    if sources.is_empty() {
+        return Vec::new();
+    }
+    let text = parts
+        .iter()
+        .map(|p| p.trim())
+        .filter(|p| !p.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    let anchors: Vec<_> = sentence_ranges(&text).into_iter().map(|r| r.end).collect();
+
+    let lengths: Vec<_> = sources.iter().map(|s| s.chars().count()).collect();
+    let total = lengths.iter().sum::<usize>().max(1) as f64;
+
+    let mut cuts = Vec::with_capacity(sources.len());
+    let mut covered = 0;
+    let mut last = 0;
+    for length in lengths.iter().take(sources.len().saturating_sub(1)) {
+        covered += length;
+        let target = (covered as f64 / total * text.len() as f64) as usize;
+        let cut = anchors
+            .iter()
+            .copied()
+            .filter(|&a| a >= last)
+            .min_by_key(|&a| a.abs_diff(target))
+            .unwrap_or(last);
+        cuts.push(cut);
+        last = cut;
+    }
+    cuts.push(text.len());
+
+    let mut start = 0;
+    cuts.into_iter()
+        .map(|end| {
+            let part = text[start..end].trim().to_string();
+            start = end;
+            part
+        })
+        .collect()
+}
@@ -1,31 +1,126 @@
 use crate::{
     controller::{
-        DEFAULT_STYLESHEET, get_ordered_path,
+        get_ordered_path,
+        layout::horizontal_css,
+        notes::{noteref, take_notes, write_footnotes},
+        resource::{ResourceMap, normalize_path, update_css_urls},
+        structure::{is_structured, reinject_units},
+        style::OutputStyle,
         xml::{
-            count_lines, extract_head, image_position, remove_part_tags, to_xml,
-            update_image_paths, update_style_path, update_tag_path,
+            IMAGE_MARKER_RE, count_lines, extract_head, image_position, is_stylesheet_link,
+            link_build_stylesheet, remove_part_tags, strip_syosetu_tags, to_xml, update_links,
+            update_tag_path,
         },
     },
     error::{Error, Result},
-    model::format::{EpubMetadata, FormatPage},
+    model::format::{EpubMetadata, TocEntry},
 };
+use chrono::{DateTime, NaiveDate, NaiveTime, Utc};
 use epub::doc::{EpubDoc, ResourceItem};
-use epub_builder::{EpubBuilder, EpubContent, EpubVersion, ZipLibrary};
+use epub_builder::{
+    EpubBuilder, EpubContent, EpubVersion, PageDirection, Toc, TocElement, ZipLibrary,
+};
 use quick_xml::{
     Reader, Writer,
-    events::{BytesDecl, BytesEnd, BytesStart, Event},
+    escape::escape,
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
 };
+use regex::{Captures, Regex};
 use std::{
     borrow::Cow,
-    collections::{HashMap, HashSet, VecDeque},
-    io::{self, Cursor},
+    collections::{HashMap, VecDeque},
+    io::{self, Cursor, Read, Write as _},
     mem,
-    path::PathBuf,
+    path::{Path, PathBuf},
+    sync::LazyLock,
 };
+use zip::{ZipArchive, ZipWriter, write::SimpleFileOptions};
+
+/// a dublin core element without a setter in the builder
+fn dc_element(name: &str, value: &str) -> String {
+    format!("<dc:{name}>{}</dc:{name}>", escape(value.trim()))
+}
+
+/// the legacy meta tags calibre reads the series from
+fn calibre(name: &str, value: &str) -> String {
+    format!(
+        r#"<meta name="calibre:{name}" content="{}"/>"#,
+        escape(value.trim())
+    )
+}
+
+/// the text between the first `start` and the following `end` replaced by `with`
+fn replace_between(text: &str, start: &str, end: &str, with: &str) -> String {
+    let Some(from) = text.find(start).map(|i| i + start.len()) else {
+        return text.to_string();
+    };
+    let Some(to) = text[from..].find(end).map(|i| i + from) else {
+        return text.to_string();
+    };
+    format!("{}{}{}", &text[..from], with, &text[to..])
+}
+
+/// writes what the builder cannot to the generated epub: the metadata elements without a setter
+/// and the table of contents, which it only takes one entry per page of. every other file is
+/// copied as is so the mimetype stays first and uncompressed
+fn rewrite_package(epub: Vec<u8>, elements: &[String], toc: &mut Toc) -> Result<Vec<u8>> {
+    let mut archive = ZipArchive::new(Cursor::new(epub))?;
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let name = file.name().to_string();
+        if ![".opf", "toc.ncx", "nav.xhtml"]
+            .iter()
+            .any(|e| name.ends_with(e))
+        {
+            writer.raw_copy_file(file)?;
+            continue;
+        }
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+        let text = if name.ends_with(".opf") {
+            text.replacen(
+                "</metadata>",
+                &format!("  {}\n  </metadata>", elements.join("\n    ")),
+                1,
+            )
+        } else if name.ends_with(".ncx") {
+            replace_between(&text, "<navMap>\n", "\n  </navMap>", &toc.render_epub(true))
+        } else {
+            replace_between(&text, "</h1>\n", "\n  </nav>", &toc.render(true, true))
+        };
+        let options = SimpleFileOptions::default().compression_method(file.compression());
+        writer.start_file(name, options)?;
+        writer.write_all(text.as_bytes())?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+/// accepts a full timestamp, a date or a year, as found in the source opf
+fn publication_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.to_utc());
+    }
+    let date = match date.len() {
+        4 => format!("{date}-01-01"),
+        7 => format!("{date}-01"),
+        _ => date.get(..10).unwrap_or(date).to_string(),
+    };
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()?;
+    Some(date.and_time(NaiveTime::MIN).and_utc())
+}
+
+static NOTEREF_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{noteref:(\d+)\}\}").unwrap());
+
+static MARKER_PARAGRAPH_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<p>\{\{image:(\d+)\}\}</p>").unwrap());
 
 const XHTML_MIME: &str = "application/xhtml+xml";
 const CSS_MIME: &str = "text/css";
 const JS_MIME: &str = "application/javascript";
+const NCX_MIME: &str = "application/x-dtbncx+xml";
 
 #[derive(Debug)]
 pub struct DocBuilder {
@@ -34,20 +129,34 @@ pub struct DocBuilder {
     pub name: String,
     pub pages: Vec<BuilderPage>,
     pub metadata: EpubMetadata,
+    pub toc: Vec<TocEntry>,
+    pub resources: ResourceMap,
+    pub style: OutputStyle,
 }
 
 impl DocBuilder {
     pub fn new(
         epub: EpubDoc<Cursor<Vec<u8>>>,
         name: String,
-        pages: Vec<FormatPage>,
+        pages: Vec<BuilderPage>,
         metadata: EpubMetadata,
+        toc: Vec<TocEntry>,
+        style: OutputStyle,
     ) -> Result<Self> {
+        let resources = ResourceMap::new(
+            epub.resources
+                .values()
+                .filter(|e| e.mime != NCX_MIME)
+                .map(|e| (e.path.as_path(), e.mime.as_str())),
+        );
         Ok(DocBuilder {
             epub,
+            resources,
             name,
             metadata,
-            pages: pages.into_iter().map(BuilderPage::from).collect(),
+            toc,
+            pages,
+            style,
             builder: EpubBuilder::new(ZipLibrary::new()?)?,
         })
     }
@@ -55,35 +164,73 @@ impl DocBuilder {
     pub fn build(mut self) -> Result<(Vec<u8>, String)> {
         self.builder
             .epub_version(EpubVersion::V30)
-            .stylesheet(DEFAULT_STYLESHEET)?
-            .add_language("en");
-
-        self.builder.set_title(mem::take(&mut self.metadata.title));
-
-        let authors = self
-            .metadata
-            .authors
-            .split("&")
-            .map(|e| e.trim().to_string())
-            .collect();
-
-        self.builder.set_authors(authors);
+            .epub_direction(PageDirection::Ltr)
+            .stylesheet(self.style.stylesheet().as_bytes())?;
 
+        let elements = self.add_metadata();
         self.add_images()?;
         self.add_cover_image()?;
-        self.add_style_sheets()?;
+        if self.style.keep_source_styles {
+            self.add_style_sheets()?;
+        }
         self.add_js()?;
+        self.add_other_resources()?;
 
-        for content in self.collect_contents()? {
+        let mut toc = Toc::new();
+        for content in self.collect_contents(&mut toc)? {
             self.builder.add_content(content)?;
         }
 
         let mut content = Vec::new();
         self.builder.generate(&mut content)?;
+        let content = rewrite_package(content, &elements, &mut toc)?;
 
         Ok((content, mem::take(&mut self.name)))
     }
 
+    /// sets the metadata the builder supports, returning the elements it has no setter for
+    fn add_metadata(&mut self) -> Vec<String> {
+        let metadata = mem::take(&mut self.metadata);
+        let split = |value: &str, separator: char| -> Vec<String> {
+            value
+                .split(separator)
+                .map(|e| e.trim().to_string())
+                .filter(|e| !e.is_empty())
+                .collect()
+        };
+
+        let language = match metadata.language.trim() {
+            "" => "en",
+            language => language,
+        };
+        self.builder.add_language(language);
+        self.builder.set_title(metadata.title);
+        self.builder.set_authors(split(&metadata.authors, '&'));
+        self.builder.set_subjects(split(&metadata.subjects, ','));
+
+        if !metadata.description.trim().is_empty() {
+            self.builder.add_description(metadata.description.trim());
+        }
+        if let Some(date) = publication_date(&metadata.date) {
+            self.builder.set_publication_date(date);
+        }
+
+        let mut elements = vec![];
+        if !metadata.publisher.trim().is_empty() {
+            elements.push(dc_element("publisher", &metadata.publisher));
+        }
+        for identifier in split(&metadata.identifiers, ',') {
+            elements.push(dc_element("identifier", &identifier));
+        }
+        if !metadata.series.trim().is_empty() {
+            elements.push(calibre("series", &metadata.series));
+            if !metadata.volume.trim().is_empty() {
+                elements.push(calibre("series_index", &metadata.volume));
+            }
+        }
+        elements
+    }
+
     pub fn add_cover_image(&mut self) -> Result<()> {
         let Some(cover_id) = self.epub.get_cover_id() else {
             return Ok(());
@@ -92,10 +239,7 @@ impl DocBuilder {
         let ResourceItem { path, mime, .. } = self.epub.resources.get(&cover_id).unwrap().clone();
 
         let content = self.epub.get_resource_by_path(&path).unwrap();
-        let file_name = path
-            .file_name()
-            .ok_or(Error::BuildError("Invalid file name found"))?;
-        let path = PathBuf::from("Images").join(file_name);
+        let path = self.output_path(&path)?;
 
         self.builder
             .add_cover_image(path, content.as_slice(), mime)?;
@@ -103,14 +247,20 @@ impl DocBuilder {
         Ok(())
     }
 
+    fn output_path(&self, path: &Path) -> Result<PathBuf> {
+        self.resources
+            .output(path)
+            .map(Path::to_path_buf)
+            .ok_or(Error::BuildError("Invalid file name found"))
+    }
+
     pub fn add_images(&mut self) -> Result<()> {
-        let folder = PathBuf::from("Images");
         let image_resources =
             self.get_images()
                 .into_iter()
                 .flat_map(|ResourceItem { path, mime, .. }| {
                     let content = self.epub.get_resource_by_path(&path)?;
-                    let path = folder.join(path.file_name()?);
+                    let path = self.resources.output(&path)?.to_path_buf();
                     Some((path, content, mime))
                 });
 
@@ -134,13 +284,12 @@ impl DocBuilder {
     }
 
     fn add_js(&mut self) -> Result<()> {
-        let folder = PathBuf::from("js");
         let js = self
             .get_js()
             .into_iter()
             .flat_map(|ResourceItem { path, .. }| {
                 let content = self.epub.get_resource_by_path(&path)?;
-                let path = folder.join(path.file_name()?);
+                let path = self.resources.output(&path)?.to_path_buf();
                 Some((path, content))
             });
 
@@ -163,18 +312,22 @@ impl DocBuilder {
     }
 
     fn add_style_sheets(&mut self) -> Result<()> {
-        let folder = PathBuf::from("Styles");
         let style_sheets =
             self.get_style_sheets()
                 .into_iter()
                 .flat_map(|ResourceItem { path, .. }| {
-                    let content = self.epub.get_resource_by_path(&path)?;
-                    let path = folder.join(path.file_name()?);
+                    let content = self.epub.get_resource_str_by_path(&path)?;
+                    let content = update_css_urls(&content, &path, &self.resources);
+                    let content = horizontal_css(&content).into_owned();
+                    let path = self.resources.output(&path)?.to_path_buf();
                     Some((path, content))
                 });
 
         for (path, content) in style_sheets {
-            if let Err(error) = self.builder.add_resource(path, &*content, CSS_MIME) {
+            if let Err(error) = self
+                .builder
+                .add_resource(path, content.as_bytes(), CSS_MIME)
+            {
                 log::error!("{:#?}", error);
             }
         }
@@ -191,99 +344,119 @@ impl DocBuilder {
             .collect()
     }
 
-    pub fn collect_contents(&mut self) -> Result<Vec<EpubContent<Cursor<Vec<u8>>>>> {
-        let epub_paths = get_ordered_path(&self.epub);
+    /// fonts, audio and every other manifest item not added as an image, style sheet, script or page
+    fn add_other_resources(&mut self) -> Result<()> {
+        let resources = self.get_other_resources().into_iter().flat_map(
+            |ResourceItem { path, mime, .. }| {
+                let content = self.epub.get_resource_by_path(&path)?;
+                let path = self.resources.output(&path)?.to_path_buf();
+                Some((path, content, mime))
+            },
+        );
 
-        let path_map = self.path_map();
-        let linked_files: Vec<_> = epub_paths
-            .iter()
-            .map(|stem| link_files(stem, &path_map))
-            .collect();
+        for (path, content, mime) in resources {
+            if let Err(error) = self.builder.add_resource(path, &*content, mime) {
+                log::error!("{:#?}", error);
+            }
+        }
 
-        let file_parts: Vec<_> = linked_files
-            .into_iter()
-            .map(|(md_file, xhtml_path)| {
-                let epub_buf = self.epub.get_resource_by_path(&xhtml_path).unwrap();
-                let href = to_text_path(&xhtml_path);
-                (href, md_file, epub_buf)
+        Ok(())
+    }
+
+    pub fn get_other_resources(&self) -> Vec<ResourceItem> {
+        self.epub
+            .resources
+            .values()
+            .filter(|e| {
+                !matches!(e.mime.as_str(), XHTML_MIME | CSS_MIME | JS_MIME | NCX_MIME)
+                    && !e.mime.starts_with("image")
             })
-            .collect();
+            .map(|e| e.to_owned())
+            .collect()
+    }
 
-        let chapter_file_names = self.chapter_file_names();
+    /// pages kept in the markup of the source link the build stylesheet after their own, which
+    /// are dropped when the source stylesheets are not kept, and get the scene breaks
+    fn restyle(&self, html: String) -> Result<String> {
+        let html = link_build_stylesheet(&html, self.style.keep_source_styles)?;
+        Ok(self.style.scene_breaks(&html).into_owned())
+    }
 
-        let mut contents = Vec::new();
+    /// the pages in reading order, their entries are added to the table of contents where they
+    /// nest by their level
+    pub fn collect_contents(&mut self, toc: &mut Toc) -> Result<Vec<EpubContent<Cursor<Vec<u8>>>>> {
         let pages: HashMap<_, _> = self
             .pages
             .iter()
-            .filter_map(|e| Some((e.path.file_name()?, e)))
+            .map(|e| (e.path.as_path(), e.content.as_str()))
             .collect();
 
-        let mut count = 0;
-        for (href, md_file, epub_buf) in file_parts {
-            let md_name = md_file.file_name().unwrap_or_default();
+        let mut contents = Vec::new();
+        for source in get_ordered_path(&self.epub) {
+            let Some(epub_buf) = self.epub.get_resource_by_path(&source) else {
+                log::error!("page missing from the epub: {:?}", source);
+                continue;
+            };
+            let href = self.output_path(&source)?;
             let html = str::from_utf8(&epub_buf)?;
-            let html = match pages.get(md_name) {
-                Some(e) => build_html(html, &e.content)?,
-                None => {
-                    let html = update_image_paths(html)?;
-                    update_style_path(&html)?
+            let normalized = normalize_path(&source);
+            let page = pages.get(normalized.as_path());
+            // pages rebuilt from markdown lose the ids of the source
+            let keeps_ids = page.is_none_or(|content| is_structured(content));
+            let html = match page {
+                Some(content) if !is_structured(content) => {
+                    build_html(html, content, &self.resources, &source, &self.style)?
+                }
+                Some(content) => {
+                    let content = self.style.typography.normalize(content);
+                    let html = reinject_units(html, &content, &self.resources, &source)?;
+                    self.restyle(html)?
                 }
+                None => self.restyle(update_links(html, &self.resources, &source)?)?,
             };
 
-            let file_name = href.file_name().unwrap().to_string_lossy().into_owned();
-            let mut content =
-                EpubContent::new(href.to_string_lossy(), Cursor::new(html.into_bytes()));
-            if chapter_file_names.contains(&file_name) {
-                count += 1;
-                content = content.title(format!("Chapter: {}", count));
+            let href = href.to_string_lossy();
+            let mut content = EpubContent::new(href.as_ref(), Cursor::new(html.into_bytes()));
+
+            let mut entries = self.toc.iter().filter(|e| e.path == normalized).peekable();
+            if let Some(first) = entries.peek() {
+                // the table of contents of the builder is replaced when packaging, this only
+                // keeps the page listed in it if the generated files change shape
+                content = content.title(first.title()).level(first.level as i32);
+            }
+            for entry in entries {
+                let url = match &entry.fragment {
+                    Some(fragment) if keeps_ids => format!("{}#{}", href, fragment),
+                    _ => href.to_string(),
+                };
+                toc.add(TocElement::new(url, entry.title()).level(entry.level as i32));
             }
             contents.push(content);
         }
         Ok(contents)
     }
-
-    pub fn path_map(&self) -> HashMap<Cow<'_, str>, &PathBuf> {
-        self.epub
-            .resources
-            .values()
-            .filter(|r| r.mime == XHTML_MIME)
-            .map(|ResourceItem { path, .. }| {
-                let stem = path.file_stem().unwrap().to_string_lossy();
-                (stem, path)
-            })
-            .collect()
-    }
-
-    pub fn chapter_file_names(&self) -> HashSet<String> {
-        self.epub
-            .toc
-            .iter()
-            .filter_map(|n| n.content.file_name())
-            .filter_map(|p| p.to_string_lossy().split('#').next().map(|e| e.to_string()))
-            .collect()
-    }
 }
 
-fn link_files(path: &PathBuf, path_map: &HashMap<Cow<'_, str>, &PathBuf>) -> (PathBuf, PathBuf) {
-    let file_stem = path.file_stem().unwrap();
-    let mut md_file = PathBuf::from(file_stem);
-    md_file.set_extension("md");
-
-    let xml_file = path_map.get(&file_stem.to_string_lossy()).unwrap();
-    (md_file, xml_file.into())
-}
-
-fn to_text_path(path: &PathBuf) -> PathBuf {
-    let file_name = path.file_name().unwrap();
-    PathBuf::from("Text").join(file_name)
-}
-
-fn build_html(html: &str, content: &str) -> Result<String> {
+fn build_html(
+    html: &str,
+    content: &str,
+    resources: &ResourceMap,
+    source: &Path,
+    style: &OutputStyle,
+) -> Result<String> {
     let content = remove_part_tags(content);
-    let content = replace_jp_symbols(&content);
+    let content = style.typography.normalize_markdown(&content);
+    let mut notes = vec![];
+    let content = take_notes(&content, &mut notes, |n| format!("{{{{noteref:{n}}}}}"));
+    let content = IMAGE_MARKER_RE.replace_all(&content, "\n\n$0\n\n");
     let content = to_xml(&content);
+    let content = NOTEREF_RE.replace_all(&content, |c: &Captures| noteref(c[1].parse().unwrap()));
+    let content = style.scene_breaks(&content);
 
-    let images = image_position(html)?;
+    // the markers were numbered on the page without its author notes
+    let body = strip_syosetu_tags(html)?;
+    let images = image_position(&body, resources, source)?;
+    let (content, images) = place_image_markers(&content, images)?;
     let content = add_image_tags(&content, images)?;
 
     let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
@@ -295,34 +468,66 @@ fn build_html(html: &str, content: &str) -> Result<String> {
         .with_attribute(("xmlns:epub", "http://www.idpf.org/2007/ops"))
         .with_attribute(("xml:lang", "en"))
         .write_inner_content(|writer| {
-            write_header(writer, html).map_err(io::Error::other)?;
-            write_body(writer, &content).map_err(io::Error::other)
+            write_header(writer, html, resources, source, style.keep_source_styles)
+                .map_err(io::Error::other)?;
+            write_body(writer, &content, &notes).map_err(io::Error::other)
         })?;
 
     Ok(String::from_utf8(writer.into_inner().into_inner())?)
 }
 
-fn write_header(writer: &mut Writer<Cursor<Vec<u8>>>, html: &str) -> Result<()> {
+fn write_header(
+    writer: &mut Writer<Cursor<Vec<u8>>>,
+    html: &str,
+    resources: &ResourceMap,
+    source: &Path,
+    keep_styles: bool,
+) -> Result<()> {
     let head = extract_head(html)?;
 
     writer
         .create_element("head")
-        .write_inner_content(|writer| write_head(writer, head).map_err(io::Error::other))?;
+        .write_inner_content(|writer| {
+            write_head(writer, head, resources, source, keep_styles).map_err(io::Error::other)
+        })?;
 
     Ok(())
 }
 
-pub fn write_head(writer: &mut Writer<Cursor<Vec<u8>>>, head: Cow<'_, str>) -> Result<()> {
-    let folder = PathBuf::from("../Styles");
+pub fn write_head(
+    writer: &mut Writer<Cursor<Vec<u8>>>,
+    head: Cow<'_, str>,
+    resources: &ResourceMap,
+    source: &Path,
+    keep_styles: bool,
+) -> Result<()> {
     let mut reader = Reader::from_str(&head);
     reader.config_mut().trim_text(true);
+    let mut in_style = false;
 
     loop {
         match reader.read_event()? {
+            Event::Empty(tag) if !keep_styles && is_stylesheet_link(&tag) => (),
+            Event::Start(tag) if !keep_styles && tag.name().as_ref() == b"style" => {
+                reader.read_to_end(tag.name())?;
+            }
             Event::Empty(tag) if tag.name().as_ref() == b"link" => {
-                let tag = update_tag_path(tag, &folder, "href")?;
+                let tag = update_tag_path(tag, "href", resources, source)?;
                 writer.write_event(Event::Empty(tag))?;
             }
+            Event::Start(tag) if tag.name().as_ref() == b"style" => {
+                in_style = true;
+                writer.write_event(Event::Start(tag))?;
+            }
+            Event::End(tag) if tag.name().as_ref() == b"style" => {
+                in_style = false;
+                writer.write_event(Event::End(tag))?;
+            }
+            Event::Text(text) if in_style => {
+                let css = text.decode()?;
+                let css = update_css_urls(&css, source, resources);
+                writer.write_event(Event::Text(BytesText::new(&horizontal_css(&css))))?;
+            }
             Event::Eof => break,
             e => writer.write_event(e)?,
         }
@@ -340,9 +545,10 @@ pub fn write_head(writer: &mut Writer<Cursor<Vec<u8>>>, head: Cow<'_, str>) -> R
 const ANCHOR_TAG: &[u8] = b"a";
 const DIV: &str = "div";
 
-fn write_body(writer: &mut Writer<Cursor<Vec<u8>>>, content: &str) -> Result<()> {
+fn write_body(writer: &mut Writer<Cursor<Vec<u8>>>, content: &str, notes: &[String]) -> Result<()> {
     let mut reader = Reader::from_str(content);
-    reader.config_mut().trim_text(true);
+    let is_noteref = |tag: &BytesStart| tag.try_get_attribute("epub:type").ok().flatten().is_some();
+    let mut in_noteref = false;
 
     writer
         .create_element("body")
@@ -350,6 +556,14 @@ fn write_body(writer: &mut Writer<Cursor<Vec<u8>>>, content: &str) -> Result<()>
         .write_inner_content(|writer| {
             loop {
                 match reader.read_event().map_err(io::Error::other)? {
+                    Event::Start(tag) if tag.name().as_ref() == ANCHOR_TAG && is_noteref(&tag) => {
+                        in_noteref = true;
+                        writer.write_event(Event::Start(tag))?;
+                    }
+                    Event::End(tag) if tag.name().as_ref() == ANCHOR_TAG && in_noteref => {
+                        in_noteref = false;
+                        writer.write_event(Event::End(tag))?;
+                    }
                     Event::Start(tag) if tag.name().as_ref() == ANCHOR_TAG => {
                         writer.write_event(Event::Start(BytesStart::new(DIV)))?;
                         writer.write_event(Event::Start(tag))?;
@@ -358,19 +572,66 @@ fn write_body(writer: &mut Writer<Cursor<Vec<u8>>>, content: &str) -> Result<()>
                         writer.write_event(Event::End(tag))?;
                         writer.write_event(Event::End(BytesEnd::new(DIV)))?;
                     }
+                    Event::Text(text) if is_block_whitespace(&text) => (),
                     Event::Eof => break,
                     e => writer.write_event(e)?,
                 }
             }
+            write_footnotes(writer, notes).map_err(io::Error::other)
+        })?;
+    Ok(())
+}
+
+/// puts every image whose marker survived translation in place of the marker,
+/// returning the images without a marker
+fn place_image_markers<'a>(
+    content: &str,
+    images: Vec<(BytesStart<'a>, f64)>,
+) -> Result<(String, Vec<(BytesStart<'a>, f64)>)> {
+    let mut images: Vec<_> = images.into_iter().map(Some).collect();
+    let mut placed = String::with_capacity(content.len());
+    let mut last = 0;
+
+    for captures in MARKER_PARAGRAPH_RE.captures_iter(content) {
+        let marker = captures.get(0).unwrap();
+        placed.push_str(&content[last..marker.start()]);
+        last = marker.end();
+
+        let image = captures[1]
+            .parse::<usize>()
+            .ok()
+            .and_then(|i| images.get_mut(i.checked_sub(1)?)?.take());
+        if let Some((tag, _)) = image {
+            let mut writer = Writer::new(Cursor::new(Vec::new()));
+            write_images(&mut writer, vec![tag])?;
+            placed.push_str(str::from_utf8(&writer.into_inner().into_inner())?);
+        }
+    }
+    placed.push_str(&content[last..]);
+
+    Ok((placed, images.into_iter().flatten().collect()))
+}
+
+fn write_images(writer: &mut Writer<Cursor<Vec<u8>>>, tags: Vec<BytesStart<'_>>) -> Result<()> {
+    writer
+        .create_element("div")
+        .with_attribute(("style", "text-align: center;"))
+        .write_inner_content(|writer| {
+            writer.create_element("p").write_inner_content(|writer| {
+                for tag in tags {
+                    writer.write_event(Event::Empty(tag))?;
+                }
+                Ok(())
+            })?;
             Ok(())
         })?;
     Ok(())
 }
 
+/// places the images by their relative position among the paragraphs
 fn add_image_tags(content: &str, mut images: Vec<(BytesStart<'_>, f64)>) -> Result<String> {
     let lines = count_lines(&content)?;
     let mut reader = Reader::from_str(content);
-    reader.config_mut().trim_text(true);
 
     let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
 
@@ -390,22 +651,12 @@ fn add_image_tags(content: &str, mut images: Vec<(BytesStart<'_>, f64)>) -> Resu
                 }
 
                 if image_tags.len() != 0 {
-                    writer
-                        .create_element("div")
-                        .with_attribute(("style", "text-align: center;"))
-                        .write_inner_content(|writer| {
-                            writer.create_element("p").write_inner_content(|writer| {
-                                for tag in image_tags {
-                                    writer.write_event(Event::Empty(tag))?;
-                                }
-                                Ok(())
-                            })?;
-                            Ok(())
-                        })?;
+                    write_images(&mut writer, image_tags)?;
                 }
 
                 writer.write_event(Event::Start(tag))?;
             }
+            Event::Text(text) if is_block_whitespace(&text) => (),
             Event::Eof => break,
             e => writer.write_event(e)?,
         }
@@ -414,24 +665,15 @@ fn add_image_tags(content: &str, mut images: Vec<(BytesStart<'_>, f64)>) -> Resu
     Ok(String::from_utf8(writer.into_inner().into_inner())?)
 }
 
-pub fn replace_jp_symbols(text: &str) -> String {
-    text.replace("」", "\"")
-        .replace("「", "\"")
-        .replace("』", "\"")
-        .replace("『", "\"")
+/// the whitespace between blocks, dropped so the writer can indent, while the spaces around
+/// inline elements are kept
+fn is_block_whitespace(text: &BytesText<'_>) -> bool {
+    text.iter().all(u8::is_ascii_whitespace) && text.contains(&b'\n')
 }
 
+/// the translation of a page, keyed by the normalized path of its spine item
 #[derive(Debug)]
 pub struct BuilderPage {
     pub path: PathBuf,
     pub content: String,
 }
-
-impl From<FormatPage> for BuilderPage {
-    fn from(FormatPage { path, content, .. }: FormatPage) -> Self {
-        BuilderPage {
-            path,
-            content: content,
-        }
-    }
-}
@@ -1,8 +1,11 @@
 use crate::{
     actions::{consensus_action::ConsensusAction, trans_action::TransAction},
-    controller::prompts::{CONSENSUS_PROMPT, TRANSLATION_PROMPT},
+    controller::{
+        parse::remove_think_tags,
+        prompts::{CONSENSUS_PROMPT, TRANSLATION_PROMPT},
+    },
     error::{Error, Result},
-    model::server::Think,
+    model::server::{Settings, Think},
 };
 use iced::Task;
 use reqwest_middleware::{ClientBuilder as MiddlewareBuilder, ClientWithMiddleware};
@@ -10,7 +13,7 @@ use reqwest_retry::{RetryTransientMiddleware, policies::ExponentialBackoff};
 use rig_core::{
     agent::{MultiTurnStreamItem, StreamingError},
     client::{CompletionClient, ModelListingClient, Nothing},
-    completion::{CompletionError, GetTokenUsage},
+    completion::{CompletionError, GetTokenUsage, Prompt},
     message::Message,
     providers::ollama::{self, OllamaApiKey, OllamaExt},
     streaming::{StreamedAssistantContent, StreamingChat, StreamingPrompt},
@@ -125,7 +128,9 @@ impl Client {
         let prompt = prompt.to_string();
         let stream =
             Task::future(async move { agent.stream_prompt(prompt).await }).then(Task::stream);
-        Ok(handle_stream::<TransAction, _>(stream, None, 0, page, part))
+        Ok(handle_stream::<TransAction, _>(
+            stream, None, None, 0, page, part,
+        ))
     }
 
     pub fn translate_history(
@@ -158,6 +163,7 @@ impl Client {
         Ok(handle_stream::<TransAction, _>(
             stream,
             Some(history),
+            None,
             context_window,
             page,
             part,
@@ -185,10 +191,72 @@ impl Client {
         let stream =
             Task::future(async move { agent.stream_prompt(prompt).await }).then(Task::stream);
         Ok(handle_stream::<ConsensusAction, _>(
-            stream, None, 0, page, part,
+            stream, None, None, 0, page, part,
         ))
     }
 
+    /// the history only records the japanese of the section and the synthesized text, as the
+    /// history built from the previous sections does, not the candidates of the prompt
+    pub fn consensus_history(
+        &self,
+        prompt: String,
+        japanese: String,
+        model: String,
+        history: SharedHistory,
+        settings: &Settings,
+        page: usize,
+        part: usize,
+    ) -> Result<Task<ConsensusAction>> {
+        let Client::Ollama(client) = self else {
+            return Err(Error::ServerError("server not connected"));
+        };
+        let agent = client
+            .agent(&model)
+            .preamble(CONSENSUS_PROMPT)
+            .temperature(TEMPERATURE)
+            .additional_params(agent_params(settings.think))
+            .build();
+
+        let chat_history = history.clone();
+        let stream = Task::future(async move {
+            let chat_history = chat_history.lock().unwrap().to_vec();
+            agent.stream_chat(prompt, chat_history).await
+        })
+        .then(Task::stream);
+        Ok(handle_stream::<ConsensusAction, _>(
+            stream,
+            Some(history),
+            Some(japanese),
+            settings.context_window,
+            page,
+            part,
+        ))
+    }
+
+    /// runs a prompt to completion without streaming, used where the output is not displayed live
+    pub fn complete(
+        &self,
+        preamble: &'static str,
+        prompt: String,
+        model: &str,
+        think: Think,
+    ) -> Result<Task<Result<String>>> {
+        let Client::Ollama(client) = self else {
+            return Err(Error::ServerError("server not connected"));
+        };
+        let agent = client
+            .agent(model)
+            .preamble(preamble)
+            .temperature(TEMPERATURE)
+            .additional_params(agent_params(think))
+            .build();
+
+        Ok(Task::future(async move {
+            let response = agent.prompt(prompt).await?;
+            Ok(remove_think_tags(&response))
+        }))
+    }
+
     pub fn connected(&self) -> bool {
         !matches!(self, Client::Disconnected)
     }
@@ -202,9 +270,12 @@ fn agent_params(think: Think) -> Value {
     })
 }
 
+/// a finished response is added to the history, as the `source` and the response when given,
+/// otherwise as the prompt and the response
 fn handle_stream<A, R>(
     stream: Task<std::result::Result<MultiTurnStreamItem<R>, StreamingError>>,
     history: Option<SharedHistory>,
+    source: Option<String>,
     context_window: usize,
     page: usize,
     part: usize,
@@ -225,11 +296,17 @@ where
                     Some(t.text)
                 }
                 MultiTurnStreamItem::FinalResponse(response) => {
-                    if let Some(history) = &history
-                        && let Some(hist) = response.history()
-                    {
+                    if let Some(history) = &history {
                         let mut shared = history.lock().unwrap();
-                        shared.extend_from_slice(hist);
+                        match &source {
+                            Some(source) => shared.extend([
+                                Message::user(source),
+                                Message::assistant(remove_think_tags(response.response())),
+                            ]),
+                            None => {
+                                shared.extend_from_slice(response.history().unwrap_or_default())
+                            }
+                        }
                         shift_history(&mut shared, context_window);
                     }
                     None
@@ -0,0 +1,124 @@
+use crate::model::page::jaccard;
+use regex::Regex;
+use std::{ops::Range, sync::LazyLock};
+
+static TOKEN_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s*\S+\s*|\s+").unwrap());
+
+const SENTENCE_END: [char; 5] = ['.', '!', '?', '…', '\n'];
+const CLOSING: [char; 9] = ['.', '!', '?', '…', '"', '\'', '”', '’', ')'];
+/// sentences at or above this word overlap are treated as the same sentence
+pub const SENTENCE_TOLERANCE: f64 = 0.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffKind {
+    Equal,
+    /// only in the compared text
+    Added,
+    /// only in the reference text
+    Removed,
+}
+
+/// word level diff of a text against a reference, the ranges index into whichever text holds the word
+pub fn word_diff(text: &str, reference: &str) -> Vec<(DiffKind, Range<usize>)> {
+    let a: Vec<_> = tokens(text).collect();
+    let b: Vec<_> = tokens(reference).collect();
+    let word = |text: &str, range: &Range<usize>| text[range.clone()].trim().to_string();
+    let a_words: Vec<_> = a.iter().map(|r| word(text, r)).collect();
+    let b_words: Vec<_> = b.iter().map(|r| word(reference, r)).collect();
+
+    // lcs[i][j] is the longest common subsequence of a[i..] and b[j..]
+    let width = b.len() + 1;
+    let mut lcs = vec![0u32; (a.len() + 1) * width];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i * width + j] = if a_words[i] == b_words[j] {
+                lcs[(i + 1) * width + j + 1] + 1
+            } else {
+                lcs[(i + 1) * width + j].max(lcs[i * width + j + 1])
+            };
+        }
+    }
+
+    let mut diff = Vec::with_capacity(a.len().max(b.len()));
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        if a_words[i] == b_words[j] {
+            diff.push((DiffKind::Equal, a[i].clone()));
+            i += 1;
+            j += 1;
+        } else if lcs[(i + 1) * width + j] >= lcs[i * width + j + 1] {
+            diff.push((DiffKind::Added, a[i].clone()));
+            i += 1;
+        } else {
+            diff.push((DiffKind::Removed, b[j].clone()));
+            j += 1;
+        }
+    }
+    diff.extend(a[i..].iter().map(|r| (DiffKind::Added, r.clone())));
+    diff.extend(b[j..].iter().map(|r| (DiffKind::Removed, r.clone())));
+    diff
+}
+
+/// words with their trailing whitespace
+fn tokens(text: &str) -> impl Iterator<Item = Range<usize>> {
+    TOKEN_RE.find_iter(text).map(|m| m.range())
+}
+
+/// byte ranges of the sentences in the text, ending on terminal punctuation or a line break
+pub fn sentence_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        if !SENTENCE_END.contains(&c) {
+            continue;
+        }
+        let mut end = i + c.len_utf8();
+        while let Some((j, c)) = chars.next_if(|(_, c)| CLOSING.contains(c)) {
+            end = j + c.len_utf8();
+        }
+        if chars.peek().is_none_or(|(_, c)| c.is_whitespace())
+            && !text[start..end].trim().is_empty()
+        {
+            ranges.push(start..end);
+            start = end;
+        }
+    }
+
+    if !text[start..].trim().is_empty() {
+        ranges.push(start..text.len());
+    }
+    ranges
+}
+
+/// for each text the sentences that have no close match in any of the other texts
+pub fn unique_sentences(texts: &[&str]) -> Vec<Vec<Range<usize>>> {
+    if texts.len() < 2 {
+        return vec![Vec::new(); texts.len()];
+    }
+    let sentences: Vec<Vec<_>> = texts.iter().map(|t| sentence_ranges(t)).collect();
+
+    sentences
+        .iter()
+        .enumerate()
+        .map(|(i, ranges)| {
+            ranges
+                .iter()
+                .filter(|range| {
+                    let sentence = &texts[i][(*range).clone()];
+                    sentences
+                        .iter()
+                        .enumerate()
+                        .filter(|(j, _)| *j != i)
+                        .all(|(j, others)| {
+                            others.iter().all(|other| {
+                                jaccard(sentence, &texts[j][other.clone()]) < SENTENCE_TOLERANCE
+                            })
+                        })
+                })
+                .cloned()
+                .collect()
+        })
+        .collect()
+}
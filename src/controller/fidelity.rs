@@ -0,0 +1,53 @@
+use std::collections::HashMap;
+
+/// character n-gram orders averaged by the score
+const MAX_ORDER: usize = 6;
+/// weight of recall over precision, chrF uses 2
+const BETA: f64 = 2.0;
+
+/// character n-gram F-score of the hypothesis against the reference, whitespace is ignored.
+/// returns a value between 0 and 1
+pub fn chrf(hypothesis: &str, reference: &str) -> f64 {
+    let hypothesis: Vec<_> = hypothesis.chars().filter(|c| !c.is_whitespace()).collect();
+    let reference: Vec<_> = reference.chars().filter(|c| !c.is_whitespace()).collect();
+
+    let (precision, recall, orders) = (1..=MAX_ORDER)
+        .filter_map(|n| {
+            let hyp = ngrams(&hypothesis, n);
+            let reference = ngrams(&reference, n);
+            let hyp_total: usize = hyp.values().sum();
+            let ref_total: usize = reference.values().sum();
+            if hyp_total == 0 || ref_total == 0 {
+                return None;
+            }
+            let matches: usize = hyp
+                .iter()
+                .map(|(gram, count)| (*count).min(reference.get(gram).copied().unwrap_or(0)))
+                .sum();
+            Some((
+                matches as f64 / hyp_total as f64,
+                matches as f64 / ref_total as f64,
+            ))
+        })
+        .fold((0.0, 0.0, 0), |(p, r, n), (precision, recall)| {
+            (p + precision, r + recall, n + 1)
+        });
+
+    if orders == 0 {
+        return 0.0;
+    }
+    let (precision, recall) = (precision / orders as f64, recall / orders as f64);
+    let beta = BETA * BETA;
+    if precision + recall == 0.0 {
+        0.0
+    } else {
+        (1.0 + beta) * precision * recall / (beta * precision + recall)
+    }
+}
+
+fn ngrams(chars: &[char], n: usize) -> HashMap<&[char], usize> {
+    chars.windows(n).fold(HashMap::new(), |mut acc, gram| {
+        *acc.entry(gram).or_default() += 1;
+        acc
+    })
+}
@@ -0,0 +1,93 @@
+use crate::error::Result;
+use quick_xml::{
+    XmlVersion,
+    events::{BytesStart, attributes::Attribute},
+};
+use regex::{Captures, Regex};
+use std::{borrow::Cow, sync::LazyLock};
+
+/// the value every vertical or right-to-left css property is set to in the english book
+static CSS_LAYOUT_RE: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    [
+        (r"writing-mode", "horizontal-tb"),
+        (r"text-combine(?:-upright|-horizontal)?", "none"),
+        (r"text-orientation", "mixed"),
+        (r"direction", "ltr"),
+        (r"unicode-bidi", "normal"),
+    ]
+    .into_iter()
+    .map(|(property, value)| {
+        let re = format!(r"(?i)(^|[\s;{{])((?:-webkit-|-epub-|-moz-|-ms-)?{property})\s*:[^;}}]*");
+        (Regex::new(&re).unwrap(), value)
+    })
+    .collect()
+});
+
+/// the classes common japanese stylesheets use for the writing direction, mapped to their
+/// horizontal class or dropped when they only apply to vertical text
+const LAYOUT_CLASSES: &[(&str, Option<&str>)] = &[
+    ("vrtl", Some("hltr")),
+    ("vltr", Some("hltr")),
+    ("hrtl", Some("hltr")),
+    ("tcy", None),
+    ("tate-chu-yoko", None),
+    ("upright", None),
+    ("sideways", None),
+];
+
+/// sets every vertical writing, text combining and right-to-left property to its horizontal value
+pub fn horizontal_css(css: &str) -> Cow<'_, str> {
+    let mut css = Cow::Borrowed(css);
+    for (re, value) in CSS_LAYOUT_RE.iter() {
+        if let Cow::Owned(replaced) = re.replace_all(&css, |captures: &Captures| {
+            format!("{}{}: {}", &captures[1], &captures[2], value)
+        }) {
+            css = Cow::Owned(replaced);
+        }
+    }
+    css
+}
+
+/// converts the `class`, `dir` and `style` attributes of a tag to a horizontal left-to-right layout
+pub fn horizontal_tag(tag: BytesStart<'_>) -> Result<BytesStart<'_>> {
+    let is_layout = |a: &Attribute| matches!(a.key.as_ref(), b"class" | b"dir" | b"style");
+    if !tag.attributes().flatten().any(|a| is_layout(&a)) {
+        return Ok(tag);
+    }
+
+    let mut updated = BytesStart::new(str::from_utf8(tag.name().as_ref())?.to_string());
+    for attribute in tag.attributes().flatten() {
+        let value = attribute.normalized_value(XmlVersion::Implicit1_0)?;
+        match attribute.key.as_ref() {
+            b"class" => {
+                let classes = horizontal_classes(&value);
+                if !classes.is_empty() {
+                    updated.push_attribute(("class", classes.as_str()));
+                }
+            }
+            b"dir" if value.eq_ignore_ascii_case("rtl") => {
+                updated.push_attribute(("dir", "ltr"));
+            }
+            b"style" => updated.push_attribute(("style", horizontal_css(&value).as_ref())),
+            _ => updated.push_attribute(attribute),
+        }
+    }
+    Ok(updated)
+}
+
+fn horizontal_classes(classes: &str) -> String {
+    let mut updated: Vec<&str> = vec![];
+    for class in classes.split_whitespace() {
+        let class = match LAYOUT_CLASSES
+            .iter()
+            .find(|(c, _)| c.eq_ignore_ascii_case(class))
+        {
+            Some((_, replacement)) => *replacement,
+            None => Some(class),
+        };
+        if let Some(class) = class.filter(|c| !updated.contains(c)) {
+            updated.push(class);
+        }
+    }
+    updated.join(" ")
+}
@@ -0,0 +1,93 @@
+use crate::{controller::diff::sentence_ranges, model::page::jaccard};
+
+/// merges candidate translations of a section without a model, sentence by sentence.
+/// returns the merged text and the candidate each sentence was taken from, none without
+/// candidates
+pub fn merge_section(candidates: &[(&str, &str)]) -> Option<(String, Vec<String>)> {
+    let sentences: Vec<Vec<&str>> = candidates
+        .iter()
+        .map(|(_, text)| {
+            sentence_ranges(text)
+                .into_iter()
+                .map(|range| &text[range])
+                .collect()
+        })
+        .collect();
+
+    // the candidate with the median sentence count decides the slots
+    let mut order: Vec<_> = (0..sentences.len()).collect();
+    order.sort_by_key(|&i| sentences[i].len());
+    let &pivot = order.get(order.len() / 2)?;
+
+    let alignments: Vec<_> = sentences
+        .iter()
+        .enumerate()
+        .map(|(i, other)| match i == pivot {
+            true => (0..other.len()).map(Some).collect(),
+            false => align_sentences(&sentences[pivot], other),
+        })
+        .collect();
+
+    let mut content = String::new();
+    let mut sources = Vec::new();
+    for (slot, pivot_sentence) in sentences[pivot].iter().enumerate() {
+        let slot_sentences: Vec<_> = alignments
+            .iter()
+            .enumerate()
+            .filter_map(|(i, alignment)| Some((i, sentences[i][alignment[slot]?].trim())))
+            .collect();
+        let (winner, sentence) = medoid(&slot_sentences);
+
+        if slot > 0 {
+            let trimmed = pivot_sentence.trim_start();
+            content.push_str(&pivot_sentence[..pivot_sentence.len() - trimmed.len()]);
+        }
+        content.push_str(sentence);
+        sources.push(candidates[winner].0.to_string());
+    }
+
+    Some((content, sources))
+}
+
+/// the sentence with the highest total similarity to the rest of the slot
+fn medoid<'a>(slot: &[(usize, &'a str)]) -> (usize, &'a str) {
+    slot.iter()
+        .map(|&(i, sentence)| {
+            let score: f64 = slot.iter().map(|(_, other)| jaccard(sentence, other)).sum();
+            (score, i, sentence)
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0).then(b.1.cmp(&a.1)))
+        .map(|(_, i, sentence)| (i, sentence))
+        .unwrap_or_default()
+}
+
+/// maps every pivot sentence to the most similar sentence of the other candidate,
+/// keeping both in order and leaving slots without a match empty
+fn align_sentences(pivot: &[&str], other: &[&str]) -> Vec<Option<usize>> {
+    let width = other.len() + 1;
+    let mut score = vec![0.0f64; (pivot.len() + 1) * width];
+    for i in (0..pivot.len()).rev() {
+        for j in (0..other.len()).rev() {
+            let matched = score[(i + 1) * width + j + 1] + jaccard(pivot[i], other[j]);
+            score[i * width + j] = matched
+                .max(score[(i + 1) * width + j])
+                .max(score[i * width + j + 1]);
+        }
+    }
+
+    let mut alignment = vec![None; pivot.len()];
+    let (mut i, mut j) = (0, 0);
+    while i < pivot.len() && j < other.len() {
+        let similarity = jaccard(pivot[i], other[j]);
+        if similarity > 0.0 && score[i * width + j] == score[(i + 1) * width + j + 1] + similarity {
+            alignment[i] = Some(j);
+            i += 1;
+            j += 1;
+        } else if score[i * width + j] == score[(i + 1) * width + j] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    alignment
+}
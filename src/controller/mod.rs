@@ -1,10 +1,24 @@
 use epub::doc::EpubDoc;
 use std::{io::Cursor, path::PathBuf};
 
+pub mod align;
 pub mod builder;
 pub mod client;
+pub mod diff;
+pub mod fidelity;
+pub mod layout;
+pub mod merge;
+pub mod notes;
 pub mod parse;
 pub mod prompts;
+pub mod resource;
+pub mod spreadsheet;
+pub mod structure;
+pub mod style;
+pub mod tmx;
+pub mod typography;
+pub mod validate;
+pub mod xliff;
 pub mod xml;
 
 pub fn get_ordered_path(epub: &EpubDoc<Cursor<Vec<u8>>>) -> Vec<PathBuf> {
@@ -14,6 +28,15 @@ pub fn get_ordered_path(epub: &EpubDoc<Cursor<Vec<u8>>>) -> Vec<PathBuf> {
         .collect()
 }
 
+/// a source/target pair addressed by page path and part, used to exchange translations with other tools
+#[derive(Debug, Default, Clone)]
+pub struct Segment {
+    pub path: PathBuf,
+    pub part: usize,
+    pub source: String,
+    pub target: String,
+}
+
 pub fn part_tag(n: usize) -> String {
     format!("\n\n<part>{}</part>\n\n", n)
 }
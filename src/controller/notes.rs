@@ -0,0 +1,65 @@
+use crate::error::Result;
+use quick_xml::{
+    Writer,
+    events::{BytesText, Event},
+};
+use regex::{Captures, Regex};
+use std::{borrow::Cow, io::Write, sync::LazyLock};
+
+/// a translator note left in the text as `{{note:explanation}}` right after the term it explains
+pub static NOTE_MARKER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?s)\{\{note:(.*?)\}\}").unwrap());
+
+pub fn note_marker(text: &str) -> String {
+    format!("{{{{note:{}}}}}", text.trim())
+}
+
+/// replaces every note marker with the result of `reference`, called with the number of the
+/// note in its page, returning the text and the notes in order
+pub fn take_notes<'a>(
+    text: &'a str,
+    notes: &mut Vec<String>,
+    reference: impl Fn(usize) -> String,
+) -> Cow<'a, str> {
+    NOTE_MARKER_RE.replace_all(text, |captures: &Captures| {
+        notes.push(captures[1].trim().to_string());
+        reference(notes.len())
+    })
+}
+
+/// prefixes the ids of the notes, pages kept in the markup of the source keep its ids
+const ID_PREFIX: &str = "tl";
+
+/// the EPUB3 reference to the note with the given number in its page
+pub fn noteref(n: usize) -> String {
+    format!(
+        r##"<a epub:type="noteref" class="footnote-ref" href="#{ID_PREFIX}-note-{n}" id="{ID_PREFIX}-noteref-{n}">{n}</a>"##
+    )
+}
+
+/// writes the notes of a page as EPUB3 footnotes, each linking back to its reference
+pub fn write_footnotes<W: Write>(writer: &mut Writer<W>, notes: &[String]) -> Result<()> {
+    for (i, note) in notes.iter().enumerate() {
+        let n = i + 1;
+        let id = format!("{ID_PREFIX}-note-{n}");
+        let back = format!("#{ID_PREFIX}-noteref-{n}");
+        writer
+            .create_element("aside")
+            .with_attributes([
+                ("epub:type", "footnote"),
+                ("class", "footnote"),
+                ("id", id.as_str()),
+            ])
+            .write_inner_content(|writer| {
+                writer.create_element("p").write_inner_content(|writer| {
+                    writer
+                        .create_element("a")
+                        .with_attribute(("href", back.as_str()))
+                        .write_text_content(BytesText::new(&n.to_string()))?;
+                    writer.write_event(Event::Text(BytesText::new(&format!(" {note}"))))
+                })?;
+                Ok(())
+            })?;
+    }
+    Ok(())
+}
@@ -1,5 +1,10 @@
-use crate::controller::part_tag;
+use crate::{
+    controller::part_tag,
+    error::{Error, Result},
+};
 use regex::Regex;
+use serde::Deserialize;
+use std::ops::RangeInclusive;
 
 pub fn remove_think_tags(text: &str) -> String {
     let rg = Regex::new(r"(?s)<think>.*?</think>\s*").unwrap();
@@ -29,6 +34,16 @@ pub fn partition_text(text: &str) -> Vec<String> {
         })
 }
 
+/// the text of the first markdown heading
+pub fn first_heading(markdown: &str) -> Option<String> {
+    markdown
+        .lines()
+        .map(str::trim)
+        .find(|line| line.starts_with('#'))
+        .map(|line| line.trim_start_matches('#').trim().to_string())
+        .filter(|heading| !heading.is_empty())
+}
+
 pub fn join_partition(parts: Vec<String>) -> String {
     parts
         .into_iter()
@@ -40,3 +55,38 @@ pub fn join_partition(parts: Vec<String>) -> String {
         .collect::<Vec<_>>()
         .join("\n\n")
 }
+
+#[derive(Debug, Deserialize)]
+pub struct Judgement {
+    pub scores: Vec<JudgedScore>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct JudgedScore {
+    pub candidate: usize,
+    pub fidelity: f32,
+    pub completeness: f32,
+    pub fluency: f32,
+}
+
+/// the range of the rubric the judge is asked to score in
+const SCORE_RANGE: RangeInclusive<f32> = 1.0..=10.0;
+
+/// extracts the json object from a judge response, ignoring any text the model put around it.
+/// a score outside of the rubric rejects the judgement
+pub fn parse_judgement(text: &str) -> Result<Judgement> {
+    let text = remove_think_tags(text);
+    let (Some(start), Some(end)) = (text.find('{'), text.rfind('}')) else {
+        return Err(Error::Error(format!("No scores found in response: {text}")));
+    };
+    let judgement: Judgement = serde_json::from_str(&text[start..=end])?;
+    let invalid = judgement
+        .scores
+        .iter()
+        .flat_map(|s| [s.fidelity, s.completeness, s.fluency])
+        .find(|score| !SCORE_RANGE.contains(score));
+    if let Some(score) = invalid {
+        return Err(Error::Error(format!("Score out of range 1-10: {score}")));
+    }
+    Ok(judgement)
+}
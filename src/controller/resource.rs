@@ -0,0 +1,153 @@
+use regex::{Captures, Regex};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+    sync::LazyLock,
+};
+
+pub const TEXT_FOLDER: &str = "Text";
+pub const IMAGES_FOLDER: &str = "Images";
+pub const STYLES_FOLDER: &str = "Styles";
+pub const FONTS_FOLDER: &str = "Fonts";
+pub const AUDIO_FOLDER: &str = "Audio";
+pub const VIDEO_FOLDER: &str = "Video";
+pub const JS_FOLDER: &str = "js";
+pub const MISC_FOLDER: &str = "Misc";
+
+const FONT_EXTENSIONS: &[&str] = &["ttf", "otf", "woff", "woff2"];
+
+pub static CSS_URL_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"url\(\s*['"]?([^'")]+?)['"]?\s*\)"#).unwrap());
+
+/// the output folder of a resource, fonts are also found by extension as they are often
+/// listed with a generic media type
+pub fn resource_folder(mime: &str, path: &Path) -> &'static str {
+    let is_font = mime.contains("font")
+        || mime == "application/vnd.ms-opentype"
+        || path
+            .extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| FONT_EXTENSIONS.contains(&e.to_lowercase().as_str()));
+
+    match mime {
+        _ if is_font => FONTS_FOLDER,
+        "application/xhtml+xml" => TEXT_FOLDER,
+        "text/css" => STYLES_FOLDER,
+        "application/javascript" | "text/javascript" => JS_FOLDER,
+        mime if mime.starts_with("image/") => IMAGES_FOLDER,
+        mime if mime.starts_with("audio/") => AUDIO_FOLDER,
+        mime if mime.starts_with("video/") => VIDEO_FOLDER,
+        _ => MISC_FOLDER,
+    }
+}
+
+/// resolves `.` and `..` so paths from different documents compare equal
+pub fn normalize_path(path: impl AsRef<Path>) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.as_ref().components() {
+        match component {
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(part) => normalized.push(part),
+            _ => (),
+        }
+    }
+    normalized
+}
+
+/// the path in the archive and fragment of a reference made from `file`, external links are skipped
+pub fn resolve_href<'a>(file: &Path, href: &'a str) -> Option<(PathBuf, Option<&'a str>)> {
+    let href = href.trim();
+    let is_external = href
+        .split_once(':')
+        .is_some_and(|(scheme, _)| scheme.chars().all(|c| c.is_ascii_alphanumeric()));
+    if href.is_empty() || is_external {
+        return None;
+    }
+
+    let href = href.split('?').next().unwrap_or_default();
+    let (path, fragment) = match href.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (href, None),
+    };
+    let target = match path {
+        "" => file.to_path_buf(),
+        path => normalize_path(file.parent().unwrap_or(Path::new("")).join(path)),
+    };
+    Some((target, fragment))
+}
+
+/// the output path of every resource of the source epub, the folders are flattened into one
+/// folder per kind and colliding file names get a numbered suffix
+#[derive(Debug, Default)]
+pub struct ResourceMap {
+    outputs: HashMap<PathBuf, PathBuf>,
+}
+
+impl ResourceMap {
+    pub fn new<'a>(resources: impl IntoIterator<Item = (&'a Path, &'a str)>) -> Self {
+        let mut resources: Vec<_> = resources
+            .into_iter()
+            .map(|(path, mime)| (normalize_path(path), resource_folder(mime, path)))
+            .collect();
+        resources.sort();
+
+        // compared lowercased as some readers and file systems ignore case
+        let mut taken = HashSet::new();
+        let outputs = resources
+            .into_iter()
+            .filter_map(|(source, folder)| {
+                let output = unique_output(folder, &source, &mut taken)?;
+                Some((source, output))
+            })
+            .collect();
+        ResourceMap { outputs }
+    }
+
+    /// the output path of a resource by its source path
+    pub fn output(&self, source: impl AsRef<Path>) -> Option<&Path> {
+        self.outputs
+            .get(&normalize_path(source))
+            .map(PathBuf::as_path)
+    }
+
+    /// rewrites a reference made from the source file `from`, every output sits one folder
+    /// deep so the reference goes through the parent, external or unknown references are `None`
+    pub fn href(&self, from: &Path, href: &str) -> Option<String> {
+        let (source, fragment) = resolve_href(&normalize_path(from), href)?;
+        let output = Path::new("..").join(self.outputs.get(&source)?);
+        let output = output.to_string_lossy();
+        match fragment {
+            Some(fragment) => Some(format!("{output}#{fragment}")),
+            None => Some(output.into_owned()),
+        }
+    }
+}
+
+fn unique_output(folder: &str, source: &Path, taken: &mut HashSet<String>) -> Option<PathBuf> {
+    let stem = source.file_stem()?.to_string_lossy();
+    let extension = source
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (0..)
+        .map(|i| match i {
+            0 => format!("{stem}{extension}"),
+            i => format!("{stem}-{i}{extension}"),
+        })
+        .map(|name| Path::new(folder).join(name))
+        .find(|output| taken.insert(output.to_string_lossy().to_lowercase()))
+}
+
+/// points the `url()` references of a stylesheet at the output path of the resource
+pub fn update_css_urls<'a>(css: &'a str, css_path: &Path, resources: &ResourceMap) -> Cow<'a, str> {
+    CSS_URL_RE.replace_all(css, |captures: &Captures| {
+        match resources.href(css_path, &captures[1]) {
+            Some(url) => format!("url(\"{url}\")"),
+            None => captures[0].to_string(),
+        }
+    })
+}
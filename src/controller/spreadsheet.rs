@@ -0,0 +1,141 @@
+use crate::{
+    controller::Segment,
+    error::{Error, Result},
+    model::page::{Page, Section},
+};
+use std::{mem, path::PathBuf};
+
+const BOM: char = '\u{FEFF}';
+const HEADER: [&str; 6] = ["page", "part", "japanese", "english", "errors", "status"];
+
+pub fn to_csv(pages: &[Page]) -> String {
+    let rows = pages.iter().flat_map(|page| {
+        page.sections
+            .iter()
+            .enumerate()
+            .map(move |(part, section)| {
+                let errors: Vec<_> = page
+                    .errors
+                    .iter()
+                    .filter(|e| e.index() == part)
+                    .map(|e| e.kind())
+                    .collect();
+                let status = review_status(section, !errors.is_empty());
+                [
+                    page.path.to_string_lossy().into_owned(),
+                    (part + 1).to_string(),
+                    section.japanese.clone(),
+                    section.content.clone(),
+                    errors.join(";"),
+                    status.to_string(),
+                ]
+            })
+    });
+
+    let mut csv = String::from(BOM);
+    write_row(&mut csv, HEADER);
+    for row in rows {
+        write_row(&mut csv, row);
+    }
+    csv
+}
+
+fn review_status(section: &Section, flagged: bool) -> &'static str {
+    if section.content.trim().is_empty() {
+        "empty"
+    } else if section.open_comments().next().is_some() {
+        "commented"
+    } else if flagged {
+        "flagged"
+    } else {
+        "ok"
+    }
+}
+
+fn write_row<T: AsRef<str>>(csv: &mut String, row: impl IntoIterator<Item = T>) {
+    let fields: Vec<_> = row.into_iter().map(|f| escape_field(f.as_ref())).collect();
+    csv.push_str(&fields.join(","));
+    csv.push_str("\r\n");
+}
+
+fn escape_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// reads the page, part, japanese and english columns back into segments, rows without an
+/// english translation are skipped so they keep the current translation
+pub fn from_csv(csv: &str) -> Result<Vec<Segment>> {
+    let mut rows = parse_rows(csv.trim_start_matches(BOM)).into_iter();
+    let (_, header) = rows
+        .next()
+        .ok_or(Error::Error(String::from("csv file is empty")))?;
+    let column = |name: &str| {
+        header
+            .iter()
+            .position(|h| h.trim().eq_ignore_ascii_case(name))
+            .ok_or(Error::Error(format!("csv missing column: {name}")))
+    };
+    let (page, part, japanese, english) = (
+        column("page")?,
+        column("part")?,
+        column("japanese")?,
+        column("english")?,
+    );
+
+    rows.filter(|(_, row)| row.get(english).is_some_and(|f| !f.trim().is_empty()))
+        .map(|(line, row)| {
+            let field = |n: usize| row.get(n).cloned().unwrap_or_default();
+            let part = field(part)
+                .trim()
+                .parse::<usize>()
+                .ok()
+                .and_then(|e| e.checked_sub(1))
+                .ok_or(Error::Error(format!("csv line {line}: invalid part")))?;
+            Ok(Segment {
+                path: PathBuf::from(field(page)),
+                part,
+                source: field(japanese).replace("\r\n", "\n"),
+                target: field(english).replace("\r\n", "\n"),
+            })
+        })
+        .collect()
+}
+
+/// the rows of the file with the line each starts on, quoted fields may span several lines
+fn parse_rows(csv: &str) -> Vec<(usize, Vec<String>)> {
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = csv.chars().peekable();
+    let (mut line, mut start) = (1, 1);
+
+    while let Some(c) = chars.next() {
+        if c == '\n' {
+            line += 1;
+        }
+        match (c, quoted) {
+            ('"', true) if chars.next_if_eq(&'"').is_some() => field.push('"'),
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => row.push(mem::take(&mut field)),
+            ('\r', false) if chars.peek() == Some(&'\n') => (),
+            ('\n', false) => {
+                row.push(mem::take(&mut field));
+                rows.push((start, mem::take(&mut row)));
+                start = line;
+            }
+            (c, _) => field.push(c),
+        }
+    }
+
+    if !field.is_empty() || !row.is_empty() {
+        row.push(field);
+        rows.push((start, row));
+    }
+    rows
+}
@@ -0,0 +1,440 @@
+use crate::{
+    controller::{
+        notes::{NOTE_MARKER_RE, noteref, write_footnotes},
+        resource::ResourceMap,
+        xml::{
+            RubyPolicy, contains_author_notes, read_ruby, remove_part_tags, resolve_entity,
+            update_event,
+        },
+    },
+    error::Result,
+};
+use quick_xml::{
+    Reader, Writer,
+    events::{BytesEnd, BytesStart, BytesText, Event},
+};
+use regex::{Captures, Regex};
+use std::{collections::HashMap, fmt, io::Cursor, path::Path, sync::LazyLock};
+
+/// how the text of a page is read from the epub
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TextMode {
+    /// the page is converted to markdown and rebuilt from it
+    #[default]
+    Markdown,
+    /// the text of every block is translated in place and put back into the original markup
+    Structure,
+}
+
+impl TextMode {
+    pub const ALL: [TextMode; 2] = [TextMode::Markdown, TextMode::Structure];
+}
+
+impl fmt::Display for TextMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            TextMode::Markdown => "markdown",
+            TextMode::Structure => "keep structure",
+        };
+        write!(f, "{}", mode)
+    }
+}
+
+/// elements that hold blocks of text, the innermost ones with text become units
+const BLOCK_TAGS: &[&[u8]] = &[
+    b"body",
+    b"div",
+    b"section",
+    b"article",
+    b"aside",
+    b"header",
+    b"footer",
+    b"nav",
+    b"main",
+    b"figure",
+    b"figcaption",
+    b"blockquote",
+    b"p",
+    b"pre",
+    b"h1",
+    b"h2",
+    b"h3",
+    b"h4",
+    b"h5",
+    b"h6",
+    b"ul",
+    b"ol",
+    b"li",
+    b"dl",
+    b"dt",
+    b"dd",
+    b"table",
+    b"caption",
+    b"thead",
+    b"tbody",
+    b"tfoot",
+    b"tr",
+    b"th",
+    b"td",
+];
+
+const EPUB_NAMESPACE: &str = "http://www.idpf.org/2007/ops";
+
+/// elements whose content is never translated
+const SKIPPED_TAGS: &[&[u8]] = &[b"head", b"script", b"style"];
+
+static UNIT_MARKER_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{\{#(\d+)\}\}").unwrap());
+
+static INLINE_MARKER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{(/?)(\d+)(/?)\}\}").unwrap());
+
+static WHITESPACE_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\s+").unwrap());
+
+/// the marker at the start of the unit with the given index in its page
+pub fn unit_marker(index: usize) -> String {
+    format!("{{{{#{}}}}}", index + 1)
+}
+
+/// whether the translation was made from units rather than markdown
+pub fn is_structured(content: &str) -> bool {
+    UNIT_MARKER_RE.is_match(content)
+}
+
+/// the text of every unit of the page, one per line behind its marker, inline tags are replaced
+/// with `{{n}}`, `{{/n}}` and `{{n/}}` placeholders numbered in each unit
+pub fn extract_units(html: &str, ruby: RubyPolicy) -> Result<Vec<String>> {
+    let mut reader = Reader::from_str(html);
+    let mut units = vec![];
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if is_skipped(&tag) => {
+                reader.read_to_end(tag.name())?;
+            }
+            Event::Start(tag) if is_unit(&reader, &tag)? => {
+                let text = read_unit(&mut reader, ruby)?;
+                units.push(format!("{}{}", unit_marker(units.len()), text));
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    Ok(units)
+}
+
+/// puts the translation of every unit back into the page, units without a translation keep
+/// their text, the links are rewritten through the resource map and the notes become footnotes
+/// at the end of the body, the author notes are dropped as they were never extracted
+pub fn reinject_units(
+    html: &str,
+    translation: &str,
+    resources: &ResourceMap,
+    source: &Path,
+) -> Result<String> {
+    let translations = unit_translations(translation);
+    let mut reader = Reader::from_str(html);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut units = 0;
+    let mut notes = vec![];
+    let mut in_style = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if tag.local_name().as_ref() == b"html" => {
+                let tag = translated_root(tag)?;
+                writer.write_event(update_event(Event::Start(tag), false, resources, source)?)?;
+            }
+            Event::Start(tag) if contains_author_notes(&tag) => {
+                reader.read_to_end(tag.name())?;
+            }
+            Event::Start(tag) if is_skipped(&tag) => {
+                in_style = tag.local_name().as_ref() == b"style";
+                writer.write_event(update_event(Event::Start(tag), false, resources, source)?)?;
+            }
+            Event::Start(tag) if is_unit(&reader, &tag)? => {
+                units += 1;
+                let name = tag.name().as_ref().to_vec();
+                writer.write_event(update_event(Event::Start(tag), false, resources, source)?)?;
+                if let Some(text) = translations.get(&units) {
+                    let inline = read_inline_tags(&mut reader, resources, source)?;
+                    write_unit(&mut writer, text, &inline, &mut notes)?;
+                    writer.write_event(Event::End(BytesEnd::new(str::from_utf8(&name)?)))?;
+                }
+            }
+            Event::End(tag) if tag.local_name().as_ref() == b"body" => {
+                write_footnotes(&mut writer, &notes)?;
+                writer.write_event(Event::End(tag))?;
+            }
+            Event::End(tag) => {
+                in_style = false;
+                writer.write_event(Event::End(tag))?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(update_event(event, in_style, resources, source)?)?,
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
+
+/// the skipped elements and the syosetu preface and afterword, which are not part of the text
+fn is_skipped(tag: &BytesStart<'_>) -> bool {
+    SKIPPED_TAGS.contains(&tag.local_name().as_ref()) || contains_author_notes(tag)
+}
+
+fn is_block(tag: &BytesStart<'_>) -> bool {
+    BLOCK_TAGS.contains(&tag.local_name().as_ref())
+}
+
+/// a block is a unit when it holds text and no other block, found by reading ahead on a copy
+/// of the reader so both passes see the same units
+fn is_unit(reader: &Reader<&[u8]>, tag: &BytesStart<'_>) -> Result<bool> {
+    if !is_block(tag) {
+        return Ok(false);
+    }
+    let mut reader = reader.clone();
+    let mut depth = 0;
+    let mut has_text = false;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if is_block(&tag) => return Ok(false),
+            Event::Start(tag) if is_skipped(&tag) => {
+                reader.read_to_end(tag.name())?;
+            }
+            Event::Start(_) => depth += 1,
+            Event::End(_) if depth == 0 => return Ok(has_text),
+            Event::End(_) => depth -= 1,
+            Event::Text(text) => has_text |= !text.decode()?.trim().is_empty(),
+            Event::CData(_) | Event::GeneralRef(_) => has_text = true,
+            Event::Eof => return Ok(has_text),
+            _ => (),
+        }
+    }
+}
+
+/// reads up to the end of the unit, returning its text with the inline placeholders
+fn read_unit(reader: &mut Reader<&[u8]>, ruby: RubyPolicy) -> Result<String> {
+    let mut text = String::new();
+    let mut open = vec![];
+    let mut inline = 0;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if tag.local_name().as_ref() == b"ruby" => {
+                let (base, reading) = read_ruby(reader)?;
+                text.push_str(&ruby.text(base, &reading));
+            }
+            Event::Start(tag) if is_skipped(&tag) => {
+                reader.read_to_end(tag.name())?;
+            }
+            Event::Start(_) => {
+                inline += 1;
+                open.push(inline);
+                text.push_str(&format!("{{{{{inline}}}}}"));
+            }
+            Event::Empty(_) => {
+                inline += 1;
+                text.push_str(&format!("{{{{{inline}/}}}}"));
+            }
+            Event::End(_) => match open.pop() {
+                Some(n) => text.push_str(&format!("{{{{/{n}}}}}")),
+                None => break,
+            },
+            Event::Text(t) => text.push_str(&t.decode()?),
+            Event::CData(t) => text.push_str(&t.decode()?),
+            Event::GeneralRef(entity) => text.push_str(&resolve_entity(&entity)?),
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    Ok(WHITESPACE_RE.replace_all(text.trim(), " ").into_owned())
+}
+
+/// reads up to the end of the unit, returning its inline tags numbered as in `read_unit`
+fn read_inline_tags(
+    reader: &mut Reader<&[u8]>,
+    resources: &ResourceMap,
+    source: &Path,
+) -> Result<Vec<Event<'static>>> {
+    let mut tags = vec![];
+    let mut depth = 0;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if tag.local_name().as_ref() == b"ruby" || is_skipped(&tag) => {
+                reader.read_to_end(tag.name())?;
+            }
+            event @ (Event::Start(_) | Event::Empty(_)) => {
+                depth += matches!(event, Event::Start(_)) as usize;
+                tags.push(update_event(event, false, resources, source)?.into_owned());
+            }
+            Event::End(_) if depth == 0 => break,
+            Event::End(_) => depth -= 1,
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    Ok(tags)
+}
+
+/// writes the translated text, placeholders become their inline tag again, placeholders the
+/// model invented are dropped and tags it left open are closed at the end of the unit
+fn write_unit(
+    writer: &mut Writer<Cursor<Vec<u8>>>,
+    text: &str,
+    inline: &[Event<'static>],
+    notes: &mut Vec<String>,
+) -> Result<()> {
+    let mut open: Vec<usize> = vec![];
+    let mut last = 0;
+    let close = |writer: &mut Writer<_>, n: usize| -> Result<()> {
+        if let Some(Event::Start(tag)) = inline.get(n - 1) {
+            writer.write_event(Event::End(tag.to_end()))?;
+        }
+        Ok(())
+    };
+
+    for captures in INLINE_MARKER_RE.captures_iter(text) {
+        let marker = captures.get(0).unwrap();
+        write_text(writer, &text[last..marker.start()], notes)?;
+        last = marker.end();
+
+        let Some(n) = captures[2].parse::<usize>().ok().filter(|n| *n > 0) else {
+            continue;
+        };
+        match (inline.get(n - 1), &captures) {
+            (Some(Event::Start(tag)), c) if is_open(c) && !open.contains(&n) => {
+                writer.write_event(Event::Start(tag.borrow()))?;
+                open.push(n);
+            }
+            (Some(Event::Start(_)), c) if is_close(c) && open.contains(&n) => {
+                while let Some(m) = open.pop() {
+                    close(writer, m)?;
+                    if m == n {
+                        break;
+                    }
+                }
+            }
+            (Some(Event::Empty(tag)), c) if !is_close(c) => {
+                writer.write_event(Event::Empty(tag.borrow()))?;
+            }
+            _ => (),
+        }
+    }
+
+    write_text(writer, &text[last..], notes)?;
+    while let Some(n) = open.pop() {
+        close(writer, n)?;
+    }
+    Ok(())
+}
+
+/// writes the text with its note markers replaced by note references
+fn write_text(
+    writer: &mut Writer<Cursor<Vec<u8>>>,
+    text: &str,
+    notes: &mut Vec<String>,
+) -> Result<()> {
+    let mut last = 0;
+    for captures in NOTE_MARKER_RE.captures_iter(text) {
+        let marker = captures.get(0).unwrap();
+        writer.write_event(Event::Text(BytesText::new(&text[last..marker.start()])))?;
+        last = marker.end();
+
+        notes.push(captures[1].trim().to_string());
+        let noteref = noteref(notes.len());
+        writer.write_event(Event::Text(BytesText::from_escaped(noteref.as_str())))?;
+    }
+    writer.write_event(Event::Text(BytesText::new(&text[last..])))?;
+    Ok(())
+}
+
+fn is_open(captures: &Captures) -> bool {
+    captures[1].is_empty() && captures[3].is_empty()
+}
+
+fn is_close(captures: &Captures) -> bool {
+    !captures[1].is_empty() && captures[3].is_empty()
+}
+
+/// the translation of every unit by its number, a unit ends where the next marker starts
+fn unit_translations(translation: &str) -> HashMap<usize, String> {
+    let translation = remove_part_tags(translation);
+    let markers: Vec<_> = UNIT_MARKER_RE.captures_iter(&translation).collect();
+
+    markers
+        .iter()
+        .enumerate()
+        .filter_map(|(i, captures)| {
+            let marker = captures.get(0)?;
+            let end = markers
+                .get(i + 1)
+                .and_then(|c| c.get(0))
+                .map_or(translation.len(), |m| m.start());
+            let text = WHITESPACE_RE.replace_all(translation[marker.end()..end].trim(), " ");
+            Some((captures[1].parse().ok()?, text.into_owned()))
+        })
+        .collect()
+}
+
+/// the page is now in english, so the language of the root element is updated, and the epub
+/// namespace is declared for the footnotes
+fn translated_root(tag: BytesStart<'_>) -> Result<BytesStart<'static>> {
+    let mut updated = BytesStart::new(str::from_utf8(tag.name().as_ref())?.to_string());
+    let mut has_epub = false;
+    for attribute in tag.attributes().flatten() {
+        match attribute.key.as_ref() {
+            b"lang" | b"xml:lang" => updated.push_attribute((attribute.key.as_ref(), &b"en"[..])),
+            key => {
+                has_epub |= key == b"xmlns:epub";
+                updated.push_attribute(attribute);
+            }
+        }
+    }
+    if !has_epub {
+        updated.push_attribute(("xmlns:epub", EPUB_NAMESPACE));
+    }
+    Ok(updated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::iter::empty;
+
+    const PAGE: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<html xmlns="http://www.w3.org/1999/xhtml" xml:lang="ja">
+<head><title>一</title></head>
+<body>
+<div class="p-novel__text p-novel__text--preface"><p>前書きです。</p></div>
+<div class="p-novel__text">
+<p>一つ目の<em>段落</em>。</p>
+<p>二つ目の段落。</p>
+</div>
+<div class="p-novel__text p-novel__text--afterword"><p>後書きです。</p></div>
+</body>
+</html>"#;
+
+    #[test]
+    fn round_trip_skips_author_notes() {
+        let units = extract_units(PAGE, RubyPolicy::Drop).unwrap();
+        assert_eq!(
+            units,
+            ["{{#1}}一つ目の{{1}}段落{{/1}}。", "{{#2}}二つ目の段落。"]
+        );
+
+        let translation = "{{#1}}The first {{1}}paragraph{{/1}}.\n{{#2}}The second paragraph.";
+        let resources = ResourceMap::new(empty());
+        let html = reinject_units(PAGE, translation, &resources, Path::new("page.xhtml")).unwrap();
+
+        assert!(html.contains("<p>The first <em>paragraph</em>.</p>"));
+        assert!(html.contains("<p>The second paragraph.</p>"));
+        assert!(!html.contains("前書き"));
+        assert!(!html.contains("後書き"));
+        assert!(!html.contains("段落"));
+    }
+}
@@ -0,0 +1,273 @@
+use crate::controller::{DEFAULT_STYLESHEET, typography::Typography};
+use regex::{NoExpand, Regex};
+use std::{borrow::Cow, fmt, sync::LazyLock};
+
+/// a sans-serif theme with block paragraphs and more room between lines
+const MODERN_STYLESHEET: &str = r#"
+    body {
+        font-family: "Helvetica Neue", Helvetica, Arial, sans-serif;
+        line-height: 1.7;
+        margin: 0;
+        padding: 1em;
+        color: #222;
+        word-wrap: break-word;
+        hyphens: auto;
+    }
+
+    h1, h2, h3, h4, h5, h6 {
+        font-weight: 600;
+        line-height: 1.25;
+        margin: 1.5em 0 0.75em 0;
+        page-break-after: avoid;
+        break-after: avoid;
+    }
+
+    h1 {
+        font-size: 1.8em;
+        margin-top: 0;
+        page-break-before: always;
+        break-before: page;
+    }
+
+    p {
+        margin: 0 0 0.9em 0;
+        text-indent: 0;
+        orphans: 2;
+        widows: 2;
+    }
+
+    img {
+        max-width: 100%;
+        height: auto;
+        display: block;
+        margin: 1em auto;
+    }
+
+    hr {
+        border: none;
+        border-top: 1px solid #ddd;
+        margin: 2em 25%;
+    }
+
+    blockquote {
+        margin: 1.5em 1.5em;
+        font-style: italic;
+    }
+
+    .footnote {
+        font-size: 0.85em;
+        margin-top: 2em;
+        padding-top: 1em;
+        border-top: 1px solid #ddd;
+    }
+
+    .footnote-ref {
+        font-size: 0.75em;
+        vertical-align: super;
+        text-decoration: none;
+    }
+"#;
+
+/// only what the book needs, everything else is left to the reading system
+const MINIMAL_STYLESHEET: &str = r#"
+    h1 {
+        page-break-before: always;
+        break-before: page;
+    }
+
+    p {
+        margin: 0;
+        text-indent: 1.2em;
+    }
+
+    img {
+        max-width: 100%;
+        height: auto;
+    }
+
+    .footnote {
+        font-size: 0.85em;
+        margin-top: 2em;
+    }
+
+    .footnote-ref {
+        font-size: 0.75em;
+        vertical-align: super;
+    }
+"#;
+
+/// the rules of the scene break paragraphs that replace horizontal rules
+const SCENE_BREAK_STYLE: &str = r#"
+    p.scene-break {
+        margin: 1.5em 0;
+        text-align: center;
+        text-indent: 0;
+    }
+"#;
+
+/// a paragraph made only of these characters is a scene break, with or without attributes as
+/// pages kept in the source markup have their classes, and so is a rule, self-closed or not
+static SCENE_BREAK_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"<hr(?:\s[^>]*)?(?:/>|>(?:\s*</hr>)?)|<p(?:\s[^>]*)?>\s*[*＊◇◆☆★■□○●・※~〜-][\s*＊◇◆☆★■□○●・※~〜-]*</p>",
+    )
+    .unwrap()
+});
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum StyleTheme {
+    #[default]
+    Classic,
+    Modern,
+    Minimal,
+    /// the stylesheet loaded by the user
+    Custom,
+}
+
+impl StyleTheme {
+    pub const ALL: [StyleTheme; 4] = [
+        StyleTheme::Classic,
+        StyleTheme::Modern,
+        StyleTheme::Minimal,
+        StyleTheme::Custom,
+    ];
+}
+
+impl fmt::Display for StyleTheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let theme = match self {
+            StyleTheme::Classic => "classic",
+            StyleTheme::Modern => "modern",
+            StyleTheme::Minimal => "minimal",
+            StyleTheme::Custom => "custom",
+        };
+        write!(f, "{}", theme)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ParagraphStyle {
+    /// as the theme sets them
+    #[default]
+    Theme,
+    /// indented first lines without space between paragraphs
+    Indent,
+    /// space between paragraphs without indents
+    Block,
+}
+
+impl ParagraphStyle {
+    pub const ALL: [ParagraphStyle; 3] = [
+        ParagraphStyle::Theme,
+        ParagraphStyle::Indent,
+        ParagraphStyle::Block,
+    ];
+}
+
+impl fmt::Display for ParagraphStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let style = match self {
+            ParagraphStyle::Theme => "theme paragraphs",
+            ParagraphStyle::Indent => "indented",
+            ParagraphStyle::Block => "block",
+        };
+        write!(f, "{}", style)
+    }
+}
+
+/// the stylesheet and typography of the built epub
+#[derive(Debug, Clone)]
+pub struct OutputStyle {
+    pub theme: StyleTheme,
+    pub custom_css: Option<String>,
+    /// a css font family list, empty keeps the font of the theme
+    pub font_family: String,
+    pub paragraphs: ParagraphStyle,
+    /// replaces horizontal rules and symbol-only paragraphs, empty keeps them
+    pub scene_break: String,
+    pub justify: bool,
+    /// copies the stylesheets of the source epub and keeps them linked from its pages
+    pub keep_source_styles: bool,
+    pub typography: Typography,
+}
+
+impl Default for OutputStyle {
+    fn default() -> Self {
+        Self {
+            theme: StyleTheme::default(),
+            custom_css: None,
+            font_family: String::new(),
+            paragraphs: ParagraphStyle::default(),
+            scene_break: String::new(),
+            justify: false,
+            keep_source_styles: true,
+            typography: Typography::default(),
+        }
+    }
+}
+
+impl OutputStyle {
+    /// the theme with the typography options appended so they take precedence
+    pub fn stylesheet(&self) -> String {
+        let theme = match (self.theme, &self.custom_css) {
+            (StyleTheme::Custom, Some(css)) => css.as_str(),
+            (StyleTheme::Modern, _) => MODERN_STYLESHEET,
+            (StyleTheme::Minimal, _) => MINIMAL_STYLESHEET,
+            _ => str::from_utf8(DEFAULT_STYLESHEET).unwrap_or_default(),
+        };
+
+        let mut css = theme.to_string();
+        let font_family = self.font_family.trim();
+        if !font_family.is_empty() {
+            css.push_str(&format!(
+                "\n    body {{\n        font-family: {font_family};\n    }}\n"
+            ));
+        }
+        match self.paragraphs {
+            ParagraphStyle::Theme => (),
+            ParagraphStyle::Indent => css.push_str(
+                "\n    p {\n        margin: 0;\n        text-indent: 1.2em;\n    }\n\n    \
+                 h1 + p, h2 + p, h3 + p, p.scene-break + p {\n        text-indent: 0;\n    }\n",
+            ),
+            ParagraphStyle::Block => css.push_str(
+                "\n    p {\n        margin: 0 0 1em 0;\n        text-indent: 0;\n    }\n",
+            ),
+        }
+        if self.justify {
+            css.push_str("\n    body, p {\n        text-align: justify;\n    }\n");
+        }
+        if !self.scene_break.trim().is_empty() {
+            css.push_str(SCENE_BREAK_STYLE);
+        }
+        css
+    }
+
+    /// replaces the horizontal rules and symbol-only paragraphs of the page with the scene break
+    pub fn scene_breaks<'a>(&self, html: &'a str) -> Cow<'a, str> {
+        let glyph = self.scene_break.trim();
+        if glyph.is_empty() {
+            return html.into();
+        }
+        let glyph = quick_xml::escape::escape(glyph);
+        let scene_break = format!(r#"<p class="scene-break">{glyph}</p>"#);
+        SCENE_BREAK_RE.replace_all(html, NoExpand(&scene_break))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scene_breaks_replace_both_rule_forms() {
+        let style = OutputStyle {
+            scene_break: String::from("◇"),
+            ..Default::default()
+        };
+        let html = r#"<p>a</p><hr class="break"/><p>b</p><hr class="break"></hr><p>c</p>"#;
+        assert_eq!(
+            style.scene_breaks(html),
+            r#"<p>a</p><p class="scene-break">◇</p><p>b</p><p class="scene-break">◇</p><p>c</p>"#
+        );
+    }
+}
@@ -0,0 +1,160 @@
+use crate::{controller::xml::read_inline_text, error::Result, model::page::Page};
+use quick_xml::{
+    Reader, Writer, XmlVersion,
+    events::{BytesDecl, BytesText, Event},
+};
+use std::{collections::HashMap, io::Cursor};
+
+const SOURCE_LANG: &str = "ja";
+const TARGET_LANG: &str = "en";
+
+pub fn to_tmx(pages: &[Page]) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    writer
+        .create_element("tmx")
+        .with_attribute(("version", "1.4"))
+        .write_inner_content(|writer| {
+            writer
+                .create_element("header")
+                .with_attribute(("creationtool", env!("CARGO_PKG_NAME")))
+                .with_attribute(("creationtoolversion", env!("CARGO_PKG_VERSION")))
+                .with_attribute(("segtype", "paragraph"))
+                .with_attribute(("o-tmf", "md"))
+                .with_attribute(("adminlang", TARGET_LANG))
+                .with_attribute(("srclang", SOURCE_LANG))
+                .with_attribute(("datatype", "plaintext"))
+                .write_empty()?;
+
+            writer
+                .create_element("body")
+                .write_inner_content(|writer| {
+                    let segments = pages
+                        .iter()
+                        .flat_map(|p| p.sections.iter())
+                        .filter(|s| !s.content.trim().is_empty())
+                        .flat_map(|s| align_lines(&s.japanese, &s.content));
+
+                    for (source, target) in segments {
+                        writer.create_element("tu").write_inner_content(|writer| {
+                            for (lang, text) in [(SOURCE_LANG, source), (TARGET_LANG, target)] {
+                                writer
+                                    .create_element("tuv")
+                                    .with_attribute(("xml:lang", lang))
+                                    .write_inner_content(|writer| {
+                                        writer
+                                            .create_element("seg")
+                                            .write_text_content(BytesText::new(text))?;
+                                        Ok(())
+                                    })?;
+                            }
+                            Ok(())
+                        })?;
+                    }
+                    Ok(())
+                })?;
+            Ok(())
+        })?;
+
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
+
+/// pairs paragraphs when both sides have the same paragraph count, otherwise the whole section
+fn align_lines<'a>(japanese: &'a str, content: &'a str) -> Vec<(&'a str, &'a str)> {
+    let source: Vec<_> = paragraphs(japanese).collect();
+    let target: Vec<_> = paragraphs(content).collect();
+
+    if source.len() == target.len() {
+        source.into_iter().zip(target).collect()
+    } else {
+        vec![(japanese.trim(), content.trim())]
+    }
+}
+
+pub fn paragraphs(text: &str) -> impl Iterator<Item = &str> {
+    text.lines().map(str::trim).filter(|l| !l.is_empty())
+}
+
+/// reads every translation unit into a map of source text to target text
+pub fn from_tmx(tmx: &str) -> Result<HashMap<String, String>> {
+    let mut reader = Reader::from_str(tmx);
+    let mut memory = HashMap::new();
+    let mut lang = String::new();
+    let mut source = None;
+    let mut target = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if tag.name().as_ref() == b"tu" => {
+                source = None;
+                target = None;
+            }
+            Event::Start(tag) if tag.name().as_ref() == b"tuv" => {
+                lang = match tag.try_get_attribute("xml:lang")? {
+                    Some(attr) => attr
+                        .normalized_value(XmlVersion::Implicit1_0)?
+                        .to_lowercase(),
+                    None => String::new(),
+                };
+            }
+            Event::Start(tag) if tag.name().as_ref() == b"seg" => {
+                let text = read_inline_text(&mut reader, b"seg")?.trim().to_string();
+                if lang.starts_with(SOURCE_LANG) {
+                    source = Some(text);
+                } else if lang.starts_with(TARGET_LANG) {
+                    target = Some(text);
+                }
+            }
+            Event::End(tag) if tag.name().as_ref() == b"tu" => {
+                if let (Some(source), Some(target)) = (source.take(), target.take()) {
+                    memory.insert(source, target);
+                }
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    Ok(memory)
+}
+
+/// looks up a section in the memory, first as a whole then paragraph by paragraph
+pub fn lookup_memory(memory: &HashMap<String, String>, japanese: &str) -> Option<String> {
+    if let Some(target) = memory.get(japanese.trim()) {
+        return Some(target.clone());
+    }
+
+    let targets: Option<Vec<_>> = paragraphs(japanese)
+        .map(|p| memory.get(p).map(String::as_str))
+        .collect();
+    targets.filter(|t| !t.is_empty()).map(|t| t.join("\n\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seg_inline_elements_are_read_as_text() {
+        let tmx = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tmx version="1.4">
+  <body>
+    <tu>
+      <tuv xml:lang="ja"><seg>猫<ph x="1">&lt;br/&gt;</ph>と犬</seg></tuv>
+      <tuv xml:lang="en"><seg>The cat <bpt i="1">&lt;b&gt;</bpt>&amp;<ept i="1">&lt;/b&gt;</ept> <hi>dog</hi></seg></tuv>
+    </tu>
+  </body>
+</tmx>"#;
+        let memory = from_tmx(tmx).unwrap();
+        assert_eq!(
+            memory.get("猫と犬").map(String::as_str),
+            Some("The cat & dog")
+        );
+        assert_eq!(
+            lookup_memory(&memory, "猫と犬").as_deref(),
+            Some("The cat & dog")
+        );
+    }
+}
@@ -0,0 +1,252 @@
+use regex::Regex;
+use std::{borrow::Cow, fmt, sync::LazyLock};
+
+/// markup the typography pass leaves untouched, tags and the placeholders of the structure and
+/// image markers, the text around them is read as if they were not there
+static PROTECTED_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"</?[A-Za-z][^<>\n]*>|\{\{(?:[#/]?\d+/?|image:[^{}\n]*)\}\}").unwrap()
+});
+
+/// every way an ellipsis is written, including the doubled japanese one. dots are taken three at
+/// a time so the period ending a sentence after an ellipsis is kept
+static ELLIPSIS_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"(?:…|‥)+|・{3,}|\.{3}|\.(?: \.){2}").unwrap());
+
+/// runs of horizontal bars and dashes standing in for an em dash
+static DASH_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[―—─]{2,}|[―─]").unwrap());
+
+static WAVE_DASH_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[〜～]+").unwrap());
+
+/// a straight quote after one of these opens a quotation
+const OPENING_CONTEXT: &str = "([{<—–-/“‘";
+
+/// ascii punctuation markdown reads as syntax, escaped when a full-width form becomes one
+const MARKDOWN_SYNTAX: &str = "\\`*_[]{}#+-=~|$^!";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum QuoteStyle {
+    #[default]
+    Straight,
+    /// curly quotes, straight quotes are curled by the text around them
+    Smart,
+}
+
+impl QuoteStyle {
+    pub const ALL: [QuoteStyle; 2] = [QuoteStyle::Straight, QuoteStyle::Smart];
+
+    fn double(&self, opening: bool) -> char {
+        match (self, opening) {
+            (QuoteStyle::Straight, _) => '"',
+            (QuoteStyle::Smart, true) => '“',
+            (QuoteStyle::Smart, false) => '”',
+        }
+    }
+
+    fn single(&self, opening: bool) -> char {
+        match (self, opening) {
+            (QuoteStyle::Straight, _) => '\'',
+            (QuoteStyle::Smart, true) => '‘',
+            (QuoteStyle::Smart, false) => '’',
+        }
+    }
+}
+
+impl fmt::Display for QuoteStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let style = match self {
+            QuoteStyle::Straight => "straight quotes",
+            QuoteStyle::Smart => "smart quotes",
+        };
+        write!(f, "{}", style)
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum EllipsisStyle {
+    /// ellipses are left as they were written
+    #[default]
+    Keep,
+    Character,
+    Dots,
+}
+
+impl EllipsisStyle {
+    pub const ALL: [EllipsisStyle; 3] = [
+        EllipsisStyle::Keep,
+        EllipsisStyle::Character,
+        EllipsisStyle::Dots,
+    ];
+}
+
+impl fmt::Display for EllipsisStyle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let style = match self {
+            EllipsisStyle::Keep => "keep ellipses",
+            EllipsisStyle::Character => "…",
+            EllipsisStyle::Dots => "...",
+        };
+        write!(f, "{}", style)
+    }
+}
+
+/// the punctuation of the translation normalized to english typography when building
+#[derive(Debug, Clone)]
+pub struct Typography {
+    pub quotes: QuoteStyle,
+    /// 『』 inside 「」 become single quotes, elsewhere they are quoted like 「」
+    pub nested_single: bool,
+    pub ellipsis: EllipsisStyle,
+    /// ―― and other runs of dashes become a single em dash
+    pub dashes: bool,
+    /// ！？ and the rest of the full-width forms become their ascii form
+    pub full_width: bool,
+    /// 〜 and ～ become a tilde
+    pub wave_dash: bool,
+    /// 【】 and 〔〕 become square brackets
+    pub brackets: bool,
+}
+
+impl Default for Typography {
+    fn default() -> Self {
+        Self {
+            quotes: QuoteStyle::default(),
+            nested_single: true,
+            ellipsis: EllipsisStyle::default(),
+            dashes: true,
+            full_width: true,
+            wave_dash: true,
+            brackets: true,
+        }
+    }
+}
+
+impl Typography {
+    pub fn normalize(&self, text: &str) -> String {
+        text.split_inclusive('\n')
+            .map(|line| self.normalize_line(line, false))
+            .collect()
+    }
+
+    /// the text of a page rendered from markdown, where the full-width forms of the markdown
+    /// syntax are escaped so they stay text
+    pub fn normalize_markdown(&self, text: &str) -> String {
+        text.split_inclusive('\n')
+            .map(|line| self.normalize_line(line, true))
+            .collect()
+    }
+
+    /// the lines the pass changes, before and after
+    pub fn changes<'a>(&self, text: &'a str) -> Vec<(&'a str, String)> {
+        text.lines()
+            .filter_map(|line| {
+                let normalized = self.normalize_line(line, false);
+                (normalized != line).then_some((line, normalized))
+            })
+            .collect()
+    }
+
+    fn normalize_line(&self, line: &str, markdown: bool) -> String {
+        let mut line = Cow::Borrowed(line);
+        if self.full_width {
+            line = full_width_to_ascii(&line, markdown).into();
+        }
+        if self.wave_dash {
+            line = WAVE_DASH_RE.replace_all(&line, "~").into_owned().into();
+        }
+        if self.brackets {
+            line = line
+                .replace(['【', '〔'], "[")
+                .replace(['】', '〕'], "]")
+                .into();
+        }
+        if self.dashes {
+            line = DASH_RE.replace_all(&line, "—").into_owned().into();
+        }
+        match self.ellipsis {
+            EllipsisStyle::Keep => (),
+            EllipsisStyle::Character => {
+                line = ELLIPSIS_RE.replace_all(&line, "…").into_owned().into();
+            }
+            EllipsisStyle::Dots => {
+                line = ELLIPSIS_RE.replace_all(&line, "...").into_owned().into();
+            }
+        }
+        self.quote(&line)
+    }
+
+    /// converts the corner brackets and sets every quote to the quote style, the protected
+    /// markup is copied as is
+    fn quote(&self, line: &str) -> String {
+        let protected: Vec<_> = PROTECTED_RE.find_iter(line).map(|m| m.range()).collect();
+        let chars: Vec<_> = line
+            .char_indices()
+            .filter(|(i, _)| !protected.iter().any(|r| r.contains(i)))
+            .collect();
+
+        let mut quoted = String::with_capacity(line.len());
+        let mut corners = 0usize;
+        let mut nested = vec![];
+        let mut last = 0;
+        for (k, &(i, c)) in chars.iter().enumerate() {
+            quoted.push_str(&line[last..i]);
+            last = i + c.len_utf8();
+
+            let prev = k.checked_sub(1).map(|k| chars[k].1);
+            let opening = prev.is_none_or(|p| p.is_whitespace() || OPENING_CONTEXT.contains(p));
+            let c = match c {
+                '「' => {
+                    corners += 1;
+                    self.quotes.double(true)
+                }
+                '」' => {
+                    corners = corners.saturating_sub(1);
+                    self.quotes.double(false)
+                }
+                '『' => {
+                    let single = self.nested_single && corners > 0;
+                    nested.push(single);
+                    match single {
+                        true => self.quotes.single(true),
+                        false => self.quotes.double(true),
+                    }
+                }
+                '』' => match nested.pop().unwrap_or(self.nested_single && corners > 0) {
+                    true => self.quotes.single(false),
+                    false => self.quotes.double(false),
+                },
+                '"' => self.quotes.double(opening),
+                '“' => self.quotes.double(true),
+                '”' => self.quotes.double(false),
+                '\'' => self.quotes.single(opening),
+                '‘' => self.quotes.single(true),
+                '’' => self.quotes.single(false),
+                c => c,
+            };
+            quoted.push(c);
+        }
+        quoted.push_str(&line[last..]);
+        quoted
+    }
+}
+
+/// the full-width forms of ascii and the ideographic space, the wave dash is left to its option
+/// and the angle brackets are kept so they are not read as markup
+fn full_width_to_ascii(text: &str, markdown: bool) -> String {
+    let mut ascii = String::with_capacity(text.len());
+    for c in text.chars() {
+        let c = match c {
+            '\u{3000}' => ' ',
+            '＜' | '＞' => c,
+            '\u{FF01}'..='\u{FF5D}' => {
+                let c = char::from_u32(c as u32 - 0xFEE0).unwrap_or(c);
+                if markdown && MARKDOWN_SYNTAX.contains(c) {
+                    ascii.push('\\');
+                }
+                c
+            }
+            c => c,
+        };
+        ascii.push(c);
+    }
+    ascii
+}
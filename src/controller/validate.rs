@@ -0,0 +1,239 @@
+use crate::controller::resource::{CSS_URL_RE, normalize_path, resolve_href};
+use epub::doc::{EpubDoc, NavPoint};
+use quick_xml::{Reader, XmlVersion, events::Event};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    io::Cursor,
+    path::{Path, PathBuf},
+};
+
+const MIMETYPE: &[u8] = b"application/epub+zip";
+const XHTML_MIME: &str = "application/xhtml+xml";
+const CSS_MIME: &str = "text/css";
+const LINK_ATTRIBUTES: &[&[u8]] = &[b"src", b"href", b"xlink:href"];
+const XML_ENTITIES: &[&[u8]] = &[b"amp", b"lt", b"gt", b"quot", b"apos"];
+
+/// a problem found in the built epub and the file it was found in
+#[derive(Debug, Clone, PartialEq)]
+pub struct Issue {
+    pub file: String,
+    pub message: String,
+}
+
+impl Issue {
+    fn new(file: impl AsRef<Path>, message: impl Into<String>) -> Self {
+        Issue {
+            file: file.as_ref().to_string_lossy().into_owned(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
+}
+
+/// checks the archive layout, that every xhtml file is well-formed without duplicate ids and
+/// that every reference and toc target points into the manifest
+pub fn validate_epub(content: &[u8]) -> Vec<Issue> {
+    let mut issues = check_mimetype(content);
+    let mut epub = match EpubDoc::from_reader(Cursor::new(content.to_vec())) {
+        Ok(epub) => epub,
+        Err(error) => {
+            let message = format!("the epub cannot be opened: {error}");
+            issues.push(Issue::new("META-INF/container.xml", message));
+            return issues;
+        }
+    };
+
+    let manifest: HashMap<PathBuf, String> = epub
+        .resources
+        .values()
+        .map(|r| (normalize_path(&r.path), r.mime.clone()))
+        .collect();
+    let mut paths: Vec<_> = manifest.keys().cloned().collect();
+    paths.sort();
+
+    let mut ids = HashMap::new();
+    let mut references = vec![];
+    for path in paths {
+        let Some(data) = epub.get_resource_by_path(&path) else {
+            issues.push(Issue::new(
+                &path,
+                "in the manifest but missing from the archive",
+            ));
+            continue;
+        };
+        match manifest[&path].as_str() {
+            XHTML_MIME => {
+                let html = String::from_utf8_lossy(&data);
+                let document = check_xhtml(&html);
+                issues.extend(document.problems.into_iter().map(|e| Issue::new(&path, e)));
+                references.extend(document.links.into_iter().map(|l| (path.clone(), l)));
+                ids.insert(path, document.ids);
+            }
+            CSS_MIME => {
+                let css = String::from_utf8_lossy(&data);
+                let urls = CSS_URL_RE.captures_iter(&css);
+                references.extend(urls.map(|c| (path.clone(), c[1].to_string())));
+            }
+            _ => (),
+        }
+    }
+
+    for (file, href) in references {
+        let Some((target, fragment)) = resolve_href(&file, &href) else {
+            continue;
+        };
+        if let Some(message) = check_target(&target, fragment, &manifest, &ids) {
+            issues.push(Issue::new(&file, format!("{href} {message}")));
+        }
+    }
+
+    for point in flatten(&epub.toc) {
+        let content = point.content.to_string_lossy();
+        let (path, fragment) = match content.split_once('#') {
+            Some((path, fragment)) => (path, Some(fragment)),
+            None => (content.as_ref(), None),
+        };
+        if let Some(message) = check_target(&normalize_path(path), fragment, &manifest, &ids) {
+            let message = format!("toc entry {:?} {message}", point.label.trim());
+            issues.push(Issue::new("toc.ncx", message));
+        }
+    }
+
+    for item in epub.spine.iter() {
+        if !epub.resources.contains_key(&item.idref) {
+            let message = format!("spine item {} is not in the manifest", item.idref);
+            issues.push(Issue::new(&epub.root_file, message));
+        }
+    }
+
+    issues
+}
+
+/// the mimetype must be the first entry of the zip, stored uncompressed and without extra data
+fn check_mimetype(content: &[u8]) -> Vec<Issue> {
+    let field = |offset: usize| {
+        content
+            .get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]) as usize)
+    };
+    let (Some(method), Some(name_len), Some(extra_len)) = (field(8), field(26), field(28)) else {
+        return vec![Issue::new("mimetype", "the archive is not a zip file")];
+    };
+    if !content.starts_with(b"PK\x03\x04") {
+        return vec![Issue::new("mimetype", "the archive is not a zip file")];
+    }
+    if content.get(30..30 + name_len) != Some(b"mimetype") {
+        return vec![Issue::new(
+            "mimetype",
+            "must be the first file of the archive",
+        )];
+    }
+
+    let mut issues = vec![];
+    if method != 0 {
+        issues.push(Issue::new("mimetype", "must be stored uncompressed"));
+    }
+    if extra_len != 0 {
+        issues.push(Issue::new("mimetype", "must not have an extra field"));
+    }
+    let data = content.get(30 + name_len + extra_len..).unwrap_or_default();
+    if method == 0 && !data.starts_with(MIMETYPE) {
+        issues.push(Issue::new("mimetype", "must contain application/epub+zip"));
+    }
+    issues
+}
+
+#[derive(Debug, Default)]
+struct XhtmlDocument {
+    ids: HashSet<String>,
+    links: Vec<String>,
+    problems: Vec<String>,
+}
+
+fn check_xhtml(html: &str) -> XhtmlDocument {
+    let mut reader = Reader::from_str(html);
+    let mut document = XhtmlDocument::default();
+    let mut depth = 0usize;
+
+    loop {
+        let event = match reader.read_event() {
+            Ok(event) => event,
+            Err(error) => {
+                let position = reader.error_position();
+                let problem = format!("not well-formed at byte {position}: {error}");
+                document.problems.push(problem);
+                return document;
+            }
+        };
+        let tag = match event {
+            Event::Start(tag) => {
+                depth += 1;
+                tag
+            }
+            Event::Empty(tag) => tag,
+            Event::End(_) => {
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+            Event::GeneralRef(entity) => {
+                if !entity.is_char_ref() && !XML_ENTITIES.contains(&entity.as_ref()) {
+                    let entity = String::from_utf8_lossy(entity.as_ref());
+                    let problem = format!("undefined entity &{entity};");
+                    document.problems.push(problem);
+                }
+                continue;
+            }
+            Event::Eof => break,
+            _ => continue,
+        };
+
+        for attribute in tag.attributes().flatten() {
+            let key = attribute.key.as_ref();
+            let Ok(value) = attribute.normalized_value(XmlVersion::Implicit1_0) else {
+                continue;
+            };
+            if key == b"id" && !document.ids.insert(value.to_string()) {
+                let problem = format!("duplicate id {value}");
+                document.problems.push(problem);
+            } else if LINK_ATTRIBUTES.contains(&key) {
+                document.links.push(value.into_owned());
+            }
+        }
+    }
+
+    if depth != 0 {
+        let problem = format!("{depth} element(s) are never closed");
+        document.problems.push(problem);
+    }
+    document
+}
+
+fn check_target(
+    target: &Path,
+    fragment: Option<&str>,
+    manifest: &HashMap<PathBuf, String>,
+    ids: &HashMap<PathBuf, HashSet<String>>,
+) -> Option<String> {
+    if !manifest.contains_key(target) {
+        return Some(format!(
+            "points to {} which is not in the manifest",
+            target.display()
+        ));
+    }
+    let fragment = fragment.filter(|f| !f.is_empty())?;
+    let ids = ids.get(target)?;
+    (!ids.contains(fragment)).then(|| format!("points to a missing id in {}", target.display()))
+}
+
+fn flatten(points: &[NavPoint]) -> Vec<&NavPoint> {
+    points
+        .iter()
+        .flat_map(|point| std::iter::once(point).chain(flatten(&point.children)))
+        .collect()
+}
@@ -0,0 +1,124 @@
+use crate::{
+    controller::{Segment, xml::read_inline_text},
+    error::{Error, Result},
+    model::page::Page,
+};
+use quick_xml::{
+    Reader, Writer, XmlVersion,
+    events::{BytesDecl, BytesText, Event},
+};
+use std::{io::Cursor, path::PathBuf};
+
+const XLIFF_NS: &str = "urn:oasis:names:tc:xliff:document:2.0";
+
+pub fn to_xliff(pages: &[Page]) -> Result<String> {
+    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+
+    writer
+        .create_element("xliff")
+        .with_attribute(("xmlns", XLIFF_NS))
+        .with_attribute(("version", "2.0"))
+        .with_attribute(("srcLang", "ja"))
+        .with_attribute(("trgLang", "en"))
+        .write_inner_content(|writer| {
+            for (i, page) in pages.iter().enumerate() {
+                let id = format!("f{}", i + 1);
+                let original = page.path.to_string_lossy();
+                writer
+                    .create_element("file")
+                    .with_attribute(("id", id.as_str()))
+                    .with_attribute(("original", original.as_ref()))
+                    .write_inner_content(|writer| {
+                        for (part, section) in page.sections.iter().enumerate() {
+                            let id = (part + 1).to_string();
+                            writer
+                                .create_element("unit")
+                                .with_attribute(("id", id.as_str()))
+                                .write_inner_content(|writer| {
+                                    writer.create_element("segment").write_inner_content(
+                                        |writer| {
+                                            writer
+                                                .create_element("source")
+                                                .with_attribute(("xml:space", "preserve"))
+                                                .write_text_content(BytesText::new(
+                                                    &section.japanese,
+                                                ))?;
+                                            writer
+                                                .create_element("target")
+                                                .with_attribute(("xml:space", "preserve"))
+                                                .write_text_content(BytesText::new(
+                                                    &section.content,
+                                                ))?;
+                                            Ok(())
+                                        },
+                                    )?;
+                                    Ok(())
+                                })?;
+                        }
+                        Ok(())
+                    })?;
+            }
+            Ok(())
+        })?;
+
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
+
+pub fn from_xliff(xliff: &str) -> Result<Vec<Segment>> {
+    let mut reader = Reader::from_str(xliff);
+    let mut segments = Vec::new();
+    let mut path = PathBuf::new();
+    let mut current: Option<Segment> = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if tag.name().as_ref() == b"file" => {
+                let original =
+                    tag.try_get_attribute("original")?
+                        .ok_or(Error::Error(String::from(
+                            "xliff file missing original path",
+                        )))?;
+                path = PathBuf::from(original.normalized_value(XmlVersion::Implicit1_0)?.as_ref());
+            }
+            Event::Start(tag) if tag.name().as_ref() == b"unit" => {
+                let id = tag
+                    .try_get_attribute("id")?
+                    .ok_or(Error::Error(String::from("xliff unit missing id")))?;
+                let part = id
+                    .normalized_value(XmlVersion::Implicit1_0)?
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|e| e.checked_sub(1))
+                    .ok_or(Error::Error(String::from(
+                        "xliff unit id is not a part number",
+                    )))?;
+                current = Some(Segment {
+                    path: path.clone(),
+                    part,
+                    ..Default::default()
+                });
+            }
+            Event::Start(tag) if tag.name().as_ref() == b"source" => {
+                let text = read_inline_text(&mut reader, b"source")?;
+                if let Some(segment) = current.as_mut() {
+                    segment.source.push_str(&text);
+                }
+            }
+            Event::Start(tag) if tag.name().as_ref() == b"target" => {
+                let text = read_inline_text(&mut reader, b"target")?;
+                if let Some(segment) = current.as_mut() {
+                    segment.target.push_str(&text);
+                }
+            }
+            Event::End(tag) if tag.name().as_ref() == b"unit" => {
+                segments.extend(current.take());
+            }
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    Ok(segments)
+}
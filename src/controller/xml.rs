@@ -1,13 +1,19 @@
-use crate::error::{Error, Result};
+use crate::{
+    controller::{
+        layout::{horizontal_css, horizontal_tag},
+        resource::{ResourceMap, update_css_urls},
+    },
+    error::{Error, Result},
+};
 use bstr::ByteSlice;
 use pulldown_cmark::{Options, Parser, html::push_html};
 use quick_xml::{
     Reader, Writer, XmlVersion,
-    escape::escape,
-    events::{BytesStart, Event},
+    escape::{escape, resolve_predefined_entity},
+    events::{BytesRef, BytesStart, BytesText, Event},
 };
 use regex::Regex;
-use std::{borrow::Cow, io::Cursor, os::unix::ffi::OsStrExt, path::PathBuf};
+use std::{borrow::Cow, fmt, io::Cursor, path::Path, sync::LazyLock};
 
 pub fn to_xml(markdown: &str) -> String {
     let markdown = escape(markdown);
@@ -22,6 +28,60 @@ pub fn remove_part_tags(content: &str) -> Cow<'_, str> {
     rg.replace_all(content, "")
 }
 
+/// the text of a character reference or predefined entity, other entities are kept as written
+pub fn resolve_entity(entity: &BytesRef<'_>) -> Result<String> {
+    if let Some(c) = entity.resolve_char_ref()? {
+        return Ok(c.to_string());
+    }
+    let name = entity.decode()?;
+    Ok(match resolve_predefined_entity(&name) {
+        Some(text) => text.to_string(),
+        None => format!("&{name};"),
+    })
+}
+
+/// the text of an xliff source or target or a tmx segment up to the closing `end` tag. markers
+/// and paired codes (`<mrk>`, `<pc>`, `<g>`, `<hi>`) keep their content, placeholders (`<ph/>`,
+/// `<sc/>`, `<ec/>`) are replaced with their `equiv` text and `<cp/>` with its character, the
+/// native code of xliff 1.2 and tmx placeholders is dropped
+pub fn read_inline_text(reader: &mut Reader<&[u8]>, end: &[u8]) -> Result<String> {
+    let mut text = String::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Text(t) => text.push_str(&t.decode()?),
+            Event::CData(t) => text.push_str(&t.decode()?),
+            Event::GeneralRef(entity) => text.push_str(&resolve_entity(&entity)?),
+            Event::Empty(tag) if tag.name().as_ref() == b"cp" => text.extend(code_point(&tag)?),
+            Event::Empty(tag) => text.push_str(&attribute(&tag, "equiv")?.unwrap_or_default()),
+            Event::Start(tag)
+                if matches!(tag.name().as_ref(), b"ph" | b"bpt" | b"ept" | b"it" | b"ut") =>
+            {
+                reader.read_to_end(tag.name())?;
+            }
+            Event::End(tag) if tag.name().as_ref() == end => break,
+            Event::Eof => return Err(Error::Error(String::from("inline text not closed"))),
+            _ => (),
+        }
+    }
+
+    Ok(text)
+}
+
+fn attribute(tag: &BytesStart<'_>, name: &str) -> Result<Option<String>> {
+    Ok(tag
+        .try_get_attribute(name)?
+        .map(|a| a.normalized_value(XmlVersion::Implicit1_0))
+        .transpose()?
+        .map(|value| value.into_owned()))
+}
+
+fn code_point(tag: &BytesStart<'_>) -> Result<Option<char>> {
+    Ok(attribute(tag, "hex")?
+        .and_then(|hex| u32::from_str_radix(&hex, 16).ok())
+        .and_then(char::from_u32))
+}
+
 pub fn strip_syosetu_tags(html: &str) -> Result<String> {
     let mut reader = Reader::from_str(html);
     let mut writer = Writer::new(Cursor::new(Vec::new()));
@@ -43,22 +103,131 @@ const SYOSETU_AFTERWORD: &[u8] = b"--afterword";
 const SYOSETU_PREFACE: &[u8] = b"--preface";
 const SYOSETU_ATTRIBUTES: &[&[u8]] = &[SYOSETU_PREFACE, SYOSETU_AFTERWORD];
 
-fn contains_author_notes(tag: &BytesStart<'_>) -> bool {
+pub fn contains_author_notes(tag: &BytesStart<'_>) -> bool {
     tag.try_get_attribute("class")
         .ok()
         .flatten()
         .is_some_and(|a| SYOSETU_ATTRIBUTES.iter().any(|e| a.value.contains_str(e)))
 }
 
+/// how ruby (furigana) readings are handled when a page is read
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum RubyPolicy {
+    /// keep only the base text
+    #[default]
+    Drop,
+    /// keep the reading after the base text as `漢字(かんじ)`
+    Annotate,
+    /// keep only the base text and collect the readings into a list
+    Collect,
+}
+
+impl RubyPolicy {
+    pub const ALL: [RubyPolicy; 3] = [RubyPolicy::Drop, RubyPolicy::Annotate, RubyPolicy::Collect];
+
+    /// the text a ruby element is replaced with
+    pub fn text(&self, base: String, reading: &str) -> String {
+        match self {
+            RubyPolicy::Annotate if !reading.is_empty() => format!("{base}({reading})"),
+            _ => base,
+        }
+    }
+}
+
+impl fmt::Display for RubyPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let policy = match self {
+            RubyPolicy::Drop => "drop readings",
+            RubyPolicy::Annotate => "annotate readings",
+            RubyPolicy::Collect => "collect readings",
+        };
+        write!(f, "{}", policy)
+    }
+}
+
+/// replaces every `<ruby>` element with its text according to the policy, returning the html
+/// and the base text and reading of every ruby element
+pub fn flatten_ruby(html: &str, policy: RubyPolicy) -> Result<(String, Vec<(String, String)>)> {
+    let mut reader = Reader::from_str(html);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+    let mut readings = Vec::new();
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if tag.name().as_ref() == b"ruby" => {
+                let (base, reading) = read_ruby(&mut reader)?;
+                let text = policy.text(base.clone(), &reading);
+                writer.write_event(Event::Text(BytesText::new(&text)))?;
+                if !reading.is_empty() {
+                    readings.push((base, reading));
+                }
+            }
+            Event::Eof => break,
+            e => writer.write_event(e)?,
+        }
+    }
+
+    let html = String::from_utf8(writer.into_inner().into_inner())?;
+    Ok((html, readings))
+}
+
+/// reads up to the closing `</ruby>`, `<rb>` and bare text are the base, `<rt>` the reading and `<rp>` is dropped
+pub fn read_ruby(reader: &mut Reader<&[u8]>) -> Result<(String, String)> {
+    let mut base = String::new();
+    let mut reading = String::new();
+    let mut inside = None;
+
+    loop {
+        match reader.read_event()? {
+            Event::Start(tag) if matches!(tag.name().as_ref(), b"rt" | b"rp") => {
+                inside = Some(tag.name().as_ref().to_vec());
+            }
+            Event::End(tag) if inside.as_deref() == Some(tag.name().as_ref()) => inside = None,
+            Event::End(tag) if tag.name().as_ref() == b"ruby" => break,
+            Event::Text(text) => match inside.as_deref() {
+                Some(b"rt") => reading.push_str(text.decode()?.trim()),
+                Some(_) => (),
+                None => base.push_str(text.decode()?.trim()),
+            },
+            Event::Eof => break,
+            _ => (),
+        }
+    }
+
+    Ok((base, reading))
+}
+
+/// the placeholder left in the text for the image with the given index in its page
+pub fn image_marker(index: usize) -> String {
+    format!("{{{{image:{}}}}}", index + 1)
+}
+
+pub static IMAGE_MARKER_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{image:(\d+)\}\}").unwrap());
+
+/// removes everything but the text, images are replaced with a marker paragraph
 pub fn strip_tags(html: &str) -> Result<String> {
     let mut reader = Reader::from_str(html);
     let mut writer = Writer::new(Cursor::new(Vec::new()));
-    let tag_match = |e: &[u8]| matches!(e, b"head" | b"img" | b"image");
+    let is_image = |e: &[u8]| matches!(e, IMG_BYTES | IMAGE_BYTES);
+    let mut images = 0;
+    let mut write_marker = |writer: &mut Writer<_>| {
+        let marker = image_marker(images);
+        images += 1;
+        writer
+            .create_element("p")
+            .write_text_content(BytesText::new(&marker))
+            .map(|_| ())
+    };
 
     loop {
         match reader.read_event()? {
-            Event::Empty(tag) if tag_match(tag.name().as_ref()) => (),
-            Event::Start(tag) if tag_match(tag.name().as_ref()) => {
+            Event::Empty(tag) if is_image(tag.name().as_ref()) => write_marker(&mut writer)?,
+            Event::Start(tag) if is_image(tag.name().as_ref()) => {
+                reader.read_to_end(tag.name())?;
+                write_marker(&mut writer)?;
+            }
+            Event::Start(tag) if tag.name().as_ref() == b"head" => {
                 reader.read_to_end(tag.name())?;
             }
             e @ (Event::Start(_) | Event::End(_) | Event::Empty(_) | Event::Text(_)) => {
@@ -76,64 +245,74 @@ pub const SRC: &str = "src";
 pub const XLINK: &str = "xlink:href";
 pub const IMG_BYTES: &[u8] = b"img";
 pub const IMAGE_BYTES: &[u8] = b"image";
+pub const LINK_ATTRIBUTES: [&str; 3] = [SRC, "href", XLINK];
 
-pub fn update_image_paths(html: &str) -> Result<String> {
-    let folder = PathBuf::from("../Images");
+/// rewrites the link attributes of every tag and the urls of inline styles through the resource map
+pub fn update_links(html: &str, resources: &ResourceMap, source: &Path) -> Result<String> {
     let mut reader = Reader::from_str(html);
     reader.config_mut().trim_text(true);
     let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
+    let mut in_style = false;
 
     loop {
         match reader.read_event()? {
-            Event::Empty(tag) if tag.name().as_ref() == IMG_BYTES => {
-                let tag = update_tag_path(tag, &folder, SRC)?;
-                writer.write_event(Event::Empty(tag))?;
+            Event::Start(tag) => {
+                in_style = tag.name().as_ref() == b"style";
+                writer.write_event(update_event(Event::Start(tag), false, resources, source)?)?
             }
-            Event::Empty(tag) if tag.name().as_ref() == IMAGE_BYTES => {
-                let tag = update_tag_path(tag, &folder, XLINK)?;
-                writer.write_event(Event::Empty(tag))?;
+            Event::End(tag) => {
+                in_style = false;
+                writer.write_event(Event::End(tag))?
             }
             Event::Eof => break,
-            event => writer.write_event(event)?,
+            event => writer.write_event(update_event(event, in_style, resources, source)?)?,
         }
     }
 
     Ok(String::from_utf8(writer.into_inner().into_inner())?)
 }
 
-pub fn update_style_path(html: &str) -> Result<String> {
-    let folder = PathBuf::from("../Styles");
-    let mut reader = Reader::from_str(html);
-    reader.config_mut().trim_text(true);
-    let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
-
-    loop {
-        match reader.read_event()? {
-            Event::Empty(tag) if tag.name().as_ref() == b"link" => {
-                let tag = update_tag_path(tag, &folder, "href")?;
-                writer.write_event(Event::Empty(tag))?;
-            }
-            Event::Eof => break,
-            event => writer.write_event(event)?,
+/// rewrites the link attributes of a tag, or the urls of the text of a `<style>`, and converts
+/// either to a horizontal layout
+pub fn update_event<'a>(
+    event: Event<'a>,
+    in_style: bool,
+    resources: &ResourceMap,
+    source: &Path,
+) -> Result<Event<'a>> {
+    let update = |mut tag: BytesStart<'a>| -> Result<BytesStart<'a>> {
+        for attr in LINK_ATTRIBUTES {
+            tag = update_tag_path(tag, attr, resources, source)?;
         }
-    }
+        horizontal_tag(tag)
+    };
 
-    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+    Ok(match event {
+        Event::Start(tag) => Event::Start(update(tag)?),
+        Event::Empty(tag) => Event::Empty(update(tag)?),
+        Event::Text(text) if in_style => {
+            let css = text.decode()?;
+            let css = update_css_urls(&css, source, resources);
+            Event::Text(BytesText::new(&horizontal_css(&css)).into_owned())
+        }
+        event => event,
+    })
 }
 
+/// points the attribute at the output path of the resource, references outside of the epub are kept
 pub fn update_tag_path(
     tag: BytesStart<'_>,
-    folder: &PathBuf,
     attr: &str,
+    resources: &ResourceMap,
+    source: &Path,
 ) -> Result<BytesStart<'static>> {
     let Some(link) = tag.try_get_attribute(attr)? else {
         return Ok(tag.into_owned());
     };
-
-    let path = PathBuf::from(link.normalized_value(XmlVersion::Implicit1_0)?.as_ref());
-    let file_name = path.file_name().unwrap();
-    let path = folder.join(file_name);
-    let path = path.as_os_str();
+    let href = link.normalized_value(XmlVersion::Implicit1_0)?;
+    let Some(href) = resources.href(source, &href) else {
+        return Ok(tag.into_owned());
+    };
 
     let attributes: Vec<_> = tag
         .attributes()
@@ -143,11 +322,52 @@ pub fn update_tag_path(
 
     let tag = BytesStart::new(str::from_utf8(tag.name().as_ref())?)
         .with_attributes(attributes)
-        .with_attributes([(attr.as_bytes(), path.as_bytes())])
+        .with_attributes([(attr, href.as_str())])
         .into_owned();
     Ok(tag)
 }
 
+pub fn is_stylesheet_link(tag: &BytesStart<'_>) -> bool {
+    tag.name().as_ref() == b"link"
+        && tag
+            .try_get_attribute("rel")
+            .ok()
+            .flatten()
+            .is_some_and(|rel| rel.value.to_ascii_lowercase().contains_str("stylesheet"))
+}
+
+/// links the stylesheet of the build after the styles of the page, which are removed with their
+/// `<style>` elements unless they are kept
+pub fn link_build_stylesheet(html: &str, keep_styles: bool) -> Result<String> {
+    let mut reader = Reader::from_str(html);
+    let mut writer = Writer::new(Cursor::new(Vec::new()));
+
+    loop {
+        match reader.read_event()? {
+            Event::Empty(tag) if !keep_styles && is_stylesheet_link(&tag) => (),
+            Event::Start(tag)
+                if !keep_styles
+                    && (is_stylesheet_link(&tag) || tag.name().as_ref() == b"style") =>
+            {
+                reader.read_to_end(tag.name())?;
+            }
+            Event::End(tag) if tag.local_name().as_ref() == b"head" => {
+                writer
+                    .create_element("link")
+                    .with_attribute(("rel", "stylesheet"))
+                    .with_attribute(("type", "text/css"))
+                    .with_attribute(("href", "../stylesheet.css"))
+                    .write_empty()?;
+                writer.write_event(Event::End(tag))?;
+            }
+            Event::Eof => break,
+            event => writer.write_event(event)?,
+        }
+    }
+
+    Ok(String::from_utf8(writer.into_inner().into_inner())?)
+}
+
 pub fn extract_head(html: &str) -> Result<Cow<'_, str>> {
     let mut reader = Reader::from_str(html);
     loop {
@@ -175,8 +395,12 @@ pub fn count_lines(html: &str) -> Result<usize> {
     Ok(count)
 }
 
-pub fn image_position(html: &str) -> Result<Vec<(BytesStart<'_>, f64)>> {
-    let folder = PathBuf::from("../Images");
+/// the images of the page in document order, with their position as a fraction of the paragraphs
+pub fn image_position<'a>(
+    html: &'a str,
+    resources: &ResourceMap,
+    source: &Path,
+) -> Result<Vec<(BytesStart<'a>, f64)>> {
     let mut reader = Reader::from_str(html);
     let mut count = 0;
     let mut images = vec![];
@@ -184,12 +408,12 @@ pub fn image_position(html: &str) -> Result<Vec<(BytesStart<'_>, f64)>> {
     loop {
         match reader.read_event()? {
             Event::Start(tag) if tag.name().as_ref() == b"p" => count += 1,
-            Event::Empty(tag) if tag.name().as_ref() == IMG_BYTES => {
-                let tag = update_tag_path(tag, &folder, SRC)?;
+            Event::Empty(tag) | Event::Start(tag) if tag.name().as_ref() == IMG_BYTES => {
+                let tag = update_tag_path(tag, SRC, resources, source)?;
                 images.push((tag, count))
             }
-            Event::Empty(tag) if tag.name().as_ref() == IMAGE_BYTES => {
-                let tag = update_tag_path(tag, &folder, XLINK)?;
+            Event::Empty(tag) | Event::Start(tag) if tag.name().as_ref() == IMAGE_BYTES => {
+                let tag = update_tag_path(tag, XLINK, resources, source)?;
                 images.push((tag, count))
             }
             Event::Eof => break,
@@ -38,12 +38,18 @@ pub enum Error {
     #[error(transparent)]
     AttrError(#[from] AttrError),
 
+    #[error(transparent)]
+    EscapeError(#[from] quick_xml::escape::EscapeError),
+
     #[error(transparent)]
     Utf8Error(#[from] std::str::Utf8Error),
 
     #[error(transparent)]
     EpubBuilderError(#[from] epub_builder::Error),
 
+    #[error(transparent)]
+    ZipError(#[from] zip::result::ZipError),
+
     #[error(transparent)]
     IconError(#[from] iced::window::icon::Error),
 
@@ -56,6 +62,9 @@ pub enum Error {
     #[error(transparent)]
     StreamError(#[from] rig_core::agent::StreamingError),
 
+    #[error(transparent)]
+    PromptError(#[from] rig_core::completion::PromptError),
+
     #[error(transparent)]
     SerdeJsonError(#[from] serde_json::Error),
 }
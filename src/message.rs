@@ -31,8 +31,8 @@ impl Translator {
             Message::DocAction(action) => self.doc.perform(action),
             Message::TransAction(tab, action) => self.translation_action(tab, action),
             Message::FormatAction(action) => self.format.perform(action),
-            Message::ConsensusAction(action) => self.consensus.perform(action).map(Into::into),
-            Message::SetView(view) => self.set_view(view).into(),
+            Message::ConsensusAction(action) => self.consensus_action(action),
+            Message::SetView(view) => self.set_view(view),
             Message::ToggleSideBar => self.toggle_side_bar_collapse().into(),
             Message::SelectTab(tab) => self.set_tab(tab).into(),
             Message::CloseTab(tab) => self.close_tab(tab).into(),
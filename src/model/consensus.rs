@@ -1,6 +1,13 @@
 use crate::{
     actions::consensus_action::ConsensusAction,
-    model::{page::Page, server::Server},
+    controller::{
+        align::realign_parts,
+        diff::{DiffKind, unique_sentences, word_diff},
+        prompts::{LITERAL_TRANSLATION_PROMPT, TRANSLATION_PROMPT},
+        structure::TextMode,
+        xml::RubyPolicy,
+    },
+    model::{page::Page, server::Server, translation::Translation},
     view::DisplayType,
     widget::page_sidebar::{SidebarAction, SidebarDeps, SidebarRow},
 };
@@ -8,10 +15,19 @@ use iced::widget::button::Status;
 use iced::{
     Border, Color, Element, Length, Renderer, Theme,
     alignment::Vertical,
-    widget::{button, container, row, text},
+    color,
+    widget::{button, container, row, span, text},
 };
 use iced_aw::menu::Item;
-use std::{iter::once, path::PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    iter::once,
+    mem,
+    ops::Range,
+    path::{Path, PathBuf},
+};
 
 #[derive(Debug, Default)]
 pub struct Consensus {
@@ -22,6 +38,24 @@ pub struct Consensus {
     pub pages: Vec<Page>,
     pub translations: Vec<Vec<String>>,
     pub display: DisplayType,
+    pub generators: Vec<Generator>,
+    pub mode: ConsensusMode,
+    pub diff: Option<DiffView>,
+    pub ruby: RubyPolicy,
+    pub text_mode: TextMode,
+    /// the tabs whose pages changed since their candidates were last synced
+    pub stale_tabs: HashSet<usize>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ConsensusMode {
+    /// the model synthesizes a new translation from the candidates
+    #[default]
+    Synthesize,
+    /// the model scores the candidates, the best ones are kept on request
+    Judge,
+    /// the medoid of each aligned sentence is kept, no model is used
+    Statistical,
 }
 
 impl Consensus {
@@ -46,6 +80,118 @@ impl Consensus {
             .collect()
     }
 
+    /// marks the tab to be synced before the next consensus action when a candidate follows it
+    pub fn tab_changed(&mut self, tab: usize) {
+        if self.candidates.iter().any(|c| c.tab == Some(tab)) {
+            self.stale_tabs.insert(tab);
+        }
+    }
+
+    /// refreshes the candidates of the tabs that changed since the last sync, aligned to the
+    /// source by file stem. candidates of closed tabs keep their last pages and tabs translating
+    /// another epub are dropped, returns a line for every dropped tab and for the gaps of a tab
+    /// synced for the first time
+    pub fn sync_tabs(&mut self, translations: &BTreeMap<usize, Translation>) -> Vec<String> {
+        // without an epub there is nothing to align against yet, the tabs stay stale
+        if self.stale_tabs.is_empty() || self.pages.is_empty() {
+            return Vec::new();
+        }
+        let stale = mem::take(&mut self.stale_tabs);
+        let (pages, file_path) = (&self.pages, &self.file_path);
+        let mut report = Vec::new();
+
+        self.candidates.retain_mut(|candidate| {
+            let Some(tab) = candidate.tab.filter(|tab| stale.contains(tab)) else {
+                return true;
+            };
+            let Some(translation) = translations.get(&tab) else {
+                candidate.tab = None;
+                return true;
+            };
+            if !same_epub(translation, file_path, pages) {
+                report.push(format!(
+                    "{}: translates {}, not the consensus epub",
+                    candidate.name,
+                    translation.file_path.display()
+                ));
+                return false;
+            }
+
+            let first = candidate.pages.is_empty();
+            let (synced, gaps) = Candidate::from_tab(tab, translation, pages);
+            if first {
+                report.extend(
+                    gaps.into_iter()
+                        .map(|gap| format!("{}: {}", synced.name, gap)),
+                );
+            }
+            *candidate = synced;
+            true
+        });
+        report
+    }
+
+    /// diffs every candidate part and the consensus result against the reference text
+    pub fn build_diff(&self, part: usize, reference: DiffReference) -> Option<DiffView> {
+        let page = self.current_page()?;
+        let candidates: Vec<_> = named_candidates(&self.candidates, page)
+            .into_iter()
+            .map(|(name, parts)| (name, parts.get(part).map_or("", String::as_str)))
+            .collect();
+        let consensus = page.sections.get(part)?.content.as_str();
+        let reference_text = match &reference {
+            DiffReference::Consensus => consensus,
+            DiffReference::Candidate(name) => candidates
+                .iter()
+                .find(|(n, _)| n == name)
+                .map(|(_, text)| *text)?,
+        };
+
+        let texts: Vec<_> = candidates.iter().map(|(_, text)| *text).collect();
+        let unique = unique_sentences(&texts);
+        let compared = candidates
+            .iter()
+            .zip(unique)
+            .filter(|((name, _), _)| reference != DiffReference::Candidate(name.to_string()))
+            .map(|(&(name, text), unique)| (name.to_string(), text, unique))
+            .chain(
+                (reference != DiffReference::Consensus)
+                    .then(|| (DiffReference::Consensus.to_string(), consensus, Vec::new())),
+            );
+
+        let reference_block = DiffBlock {
+            name: reference.to_string(),
+            tokens: vec![(reference_text.to_string(), DiffKind::Equal, false)],
+        };
+        let blocks = once(reference_block)
+            .chain(compared.map(|(name, text, unique)| DiffBlock {
+                name,
+                tokens: diff_tokens(text, reference_text, &unique),
+            }))
+            .collect();
+
+        Some(DiffView {
+            part,
+            reference,
+            blocks,
+        })
+    }
+
+    /// the texts a diff can be taken against for the current page
+    pub fn diff_references(&self) -> Vec<DiffReference> {
+        let candidates = self
+            .current_page()
+            .map(|page| named_candidates(&self.candidates, page))
+            .unwrap_or_default();
+        once(DiffReference::Consensus)
+            .chain(
+                candidates
+                    .into_iter()
+                    .map(|(name, _)| DiffReference::Candidate(name.to_string())),
+            )
+            .collect()
+    }
+
     pub fn sidebar_deps(&self) -> SidebarDeps {
         let active = self.server.connected() && self.server.handles.is_empty();
         let rows = self
@@ -121,8 +267,252 @@ fn candidate_select(i: Option<usize>, folder: &str) -> Element<'_, ConsensusActi
     .into()
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, Deserialize, Serialize)]
 pub struct Candidate {
     pub name: String,
     pub pages: Vec<(PathBuf, Vec<String>)>,
+    /// the open translation tab this candidate follows, its pages are refreshed from the tab
+    #[serde(skip)]
+    pub tab: Option<usize>,
+}
+
+impl Candidate {
+    pub fn empty(name: String, pages: &[Page]) -> Self {
+        let pages = pages
+            .iter()
+            .map(|p| (p.path.clone(), vec![String::new(); p.sections.len()]))
+            .collect();
+        Candidate {
+            name,
+            pages,
+            tab: None,
+        }
+    }
+
+    /// matches loaded pages to the source by file stem and realigns the parts of pages whose part
+    /// count differs from the section count. returns the candidate and a line for every gap
+    pub fn aligned(
+        name: String,
+        loaded: Vec<(PathBuf, Vec<String>)>,
+        source: &[Page],
+    ) -> (Self, Vec<String>) {
+        let stem = |path: &Path| {
+            path.file_stem()
+                .unwrap_or_default()
+                .to_string_lossy()
+                .into_owned()
+        };
+        let mut loaded: HashMap<_, _> = loaded
+            .into_iter()
+            .map(|(path, parts)| (stem(&path), parts))
+            .collect();
+        let mut gaps = Vec::new();
+
+        let pages = source
+            .iter()
+            .map(|page| {
+                let name = stem(&page.path);
+                let sections = page.sections.len();
+                let parts = match loaded.remove(&name) {
+                    Some(parts) if parts.len() == sections => parts,
+                    Some(parts) => {
+                        gaps.push(format!(
+                            "{name}: {} parts for {sections} sections, realigned by length",
+                            parts.len()
+                        ));
+                        let japanese: Vec<_> =
+                            page.sections.iter().map(|s| s.japanese.as_str()).collect();
+                        realign_parts(&parts, &japanese)
+                    }
+                    None => {
+                        gaps.push(format!("{name}: missing"));
+                        vec![String::new(); sections]
+                    }
+                };
+                (page.path.clone(), parts)
+            })
+            .collect();
+
+        let mut extra: Vec<_> = loaded.into_keys().collect();
+        extra.sort();
+        gaps.extend(
+            extra
+                .into_iter()
+                .map(|name| format!("{name}: not in the epub")),
+        );
+
+        let candidate = Candidate {
+            name,
+            pages,
+            tab: None,
+        };
+        (candidate, gaps)
+    }
+
+    /// the parts of an open tab aligned to the source like a loaded candidate
+    pub fn from_tab(tab: usize, translation: &Translation, source: &[Page]) -> (Self, Vec<String>) {
+        let name = match &translation.server.current_model {
+            Some(model) => format!("tab {} ({})", tab + 1, model),
+            None => format!("tab {}", tab + 1),
+        };
+        let loaded = translation
+            .pages
+            .iter()
+            .map(|p| {
+                let parts = p.sections.iter().map(|s| s.content.clone()).collect();
+                (p.path.clone(), parts)
+            })
+            .collect();
+        let (mut candidate, gaps) = Candidate::aligned(name, loaded, source);
+        candidate.tab = Some(tab);
+        (candidate, gaps)
+    }
+}
+
+/// a tab translates the consensus epub when it was opened from the same file or has the same
+/// pages
+fn same_epub(translation: &Translation, file_path: &Path, pages: &[Page]) -> bool {
+    translation.file_path == file_path
+        || translation
+            .pages
+            .iter()
+            .map(|p| &p.path)
+            .eq(pages.iter().map(|p| &p.path))
+}
+
+/// a model and prompt pair used to generate a candidate translation
+#[derive(Debug, Default, Clone)]
+pub struct Generator {
+    pub model: Option<String>,
+    pub prompt: Prompt,
+}
+
+impl Generator {
+    pub fn name(&self) -> Option<String> {
+        let model = self.model.as_ref()?;
+        Some(format!("{} ({})", model, self.prompt))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Prompt {
+    #[default]
+    Standard,
+    Literal,
+}
+
+impl Prompt {
+    pub const ALL: [Prompt; 2] = [Prompt::Standard, Prompt::Literal];
+
+    pub fn preamble(self) -> &'static str {
+        match self {
+            Prompt::Standard => TRANSLATION_PROMPT,
+            Prompt::Literal => LITERAL_TRANSLATION_PROMPT,
+        }
+    }
+}
+
+impl fmt::Display for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let prompt = match self {
+            Prompt::Standard => "standard",
+            Prompt::Literal => "literal",
+        };
+        write!(f, "{}", prompt)
+    }
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum DiffReference {
+    /// the consensus result of the part
+    #[default]
+    Consensus,
+    Candidate(String),
+}
+
+impl fmt::Display for DiffReference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DiffReference::Consensus => write!(f, "consensus"),
+            DiffReference::Candidate(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+/// word diffs of a single part, built once when the view or its inputs change
+#[derive(Debug, Default)]
+pub struct DiffView {
+    pub part: usize,
+    pub reference: DiffReference,
+    pub blocks: Vec<DiffBlock>,
+}
+
+#[derive(Debug, Default)]
+pub struct DiffBlock {
+    pub name: String,
+    /// the text, how it differs from the reference and whether it is in a unique sentence
+    pub tokens: Vec<(String, DiffKind, bool)>,
+}
+
+impl DiffView {
+    pub fn spans<Link>(&self) -> Vec<text::Span<'_, Link>> {
+        self.blocks
+            .iter()
+            .flat_map(|block| {
+                let header = span(format!("\n\n{}\n\n", block.name)).color(color!(0xff0000));
+                let tokens = block.tokens.iter().map(|(text, kind, unique)| {
+                    let token = match kind {
+                        DiffKind::Equal => span(text),
+                        DiffKind::Added => span(text).color(color!(0x00ff00)),
+                        DiffKind::Removed => span(text).color(color!(0xff4040)).strikethrough(true),
+                    };
+                    token
+                        .color_maybe(unique.then_some(Color::BLACK))
+                        .background_maybe(unique.then_some(color!(0xffff00)))
+                });
+                once(header).chain(tokens)
+            })
+            .collect()
+    }
+}
+
+fn diff_tokens(
+    text: &str,
+    reference: &str,
+    unique: &[Range<usize>],
+) -> Vec<(String, DiffKind, bool)> {
+    word_diff(text, reference)
+        .into_iter()
+        .map(|(kind, range)| match kind {
+            DiffKind::Removed => (reference[range].to_string(), kind, false),
+            _ => {
+                let in_unique = unique.iter().any(|u| u.contains(&range.start));
+                (text[range].to_string(), kind, in_unique)
+            }
+        })
+        .collect()
+}
+
+/// everything needed to resume a consensus session, written next to the epub after each page
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ConsensusProject {
+    pub pages: Vec<Page>,
+    pub candidates: Vec<Candidate>,
+}
+
+/// the candidates that contain the page, matched by file stem
+pub fn named_candidates<'a>(
+    candidates: &'a [Candidate],
+    page: &Page,
+) -> Vec<(&'a str, &'a [String])> {
+    let Some(stem) = page.file_stem() else {
+        return Vec::new();
+    };
+    candidates
+        .iter()
+        .filter_map(|c| {
+            let (_, parts) = c.pages.iter().find(|(p, _)| p.file_stem() == Some(stem))?;
+            Some((c.name.as_str(), parts.as_slice()))
+        })
+        .collect()
 }
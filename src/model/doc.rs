@@ -1,5 +1,6 @@
+use crate::controller::xml::RubyPolicy;
 use epub::doc::EpubDoc;
-use std::io::Cursor;
+use std::{collections::BTreeMap, io::Cursor};
 
 #[non_exhaustive]
 #[derive(Default, Debug)]
@@ -9,4 +10,7 @@ pub struct Doc {
     pub current_page: Option<usize>,
     pub total_pages: usize,
     pub content: String,
+    pub ruby: RubyPolicy,
+    /// the reading of every ruby base text in the book, filled when readings are collected
+    pub readings: BTreeMap<String, String>,
 }
@@ -1,6 +1,10 @@
-use epub::doc::EpubDoc;
+use crate::{
+    controller::{resource::normalize_path, style::OutputStyle, validate::Issue},
+    model::server::Server,
+};
+use epub::doc::{EpubDoc, MetadataItem, NavPoint};
 use iced::widget::image::Handle;
-use std::{io::Cursor, path::PathBuf};
+use std::{borrow::Cow, collections::HashSet, fmt, io::Cursor, iter::once, path::PathBuf};
 
 #[non_exhaustive]
 #[derive(Default, Debug)]
@@ -11,6 +15,89 @@ pub struct Format {
     pub epub: Option<EpubDoc<Cursor<Vec<u8>>>>,
     pub cover: Option<Handle>,
     pub metadata: EpubMetadata,
+    pub toc: Vec<TocEntry>,
+    pub server: Server,
+    /// the last build, held back until its validation report is reviewed
+    pub built: Option<BuiltEpub>,
+    /// one entry per spine item of the epub, in reading order
+    pub mapping: Vec<PageMapping>,
+    pub style: OutputStyle,
+    /// the lines of the mapped pages the typography pass changes, shown instead of the options
+    pub typography_preview: Option<String>,
+}
+
+/// a spine item of the source epub and the index of the translated page replacing it,
+/// without a page the original is kept
+#[derive(Debug, Clone)]
+pub struct PageMapping {
+    pub source: PathBuf,
+    pub page: Option<usize>,
+}
+
+impl PageMapping {
+    pub fn file_name(&self) -> Cow<'_, str> {
+        self.source
+            .file_name()
+            .map(|f| f.to_string_lossy())
+            .unwrap_or_default()
+    }
+}
+
+/// an option of the mapping selector in the format view
+#[derive(Debug, Clone, PartialEq)]
+pub struct PageChoice {
+    pub page: Option<usize>,
+    pub name: String,
+}
+
+impl fmt::Display for PageChoice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+impl Format {
+    /// the pages not mapped to any spine item
+    pub fn orphans(&self) -> impl Iterator<Item = &FormatPage> {
+        let mapped: HashSet<_> = self.mapping.iter().filter_map(|m| m.page).collect();
+        self.pages
+            .iter()
+            .enumerate()
+            .filter(move |(i, _)| !mapped.contains(i))
+            .map(|(_, page)| page)
+    }
+
+    pub fn page_choices(&self) -> Vec<PageChoice> {
+        let original = PageChoice {
+            page: None,
+            name: String::from("original kept"),
+        };
+        let pages = self.pages.iter().enumerate().map(|(i, page)| PageChoice {
+            page: Some(i),
+            name: page.file_name().into_owned(),
+        });
+        once(original).chain(pages).collect()
+    }
+
+    pub fn page_choice(&self, mapping: &PageMapping) -> PageChoice {
+        match mapping.page.and_then(|i| self.pages.get(i)) {
+            Some(page) => PageChoice {
+                page: mapping.page,
+                name: page.file_name().into_owned(),
+            },
+            None => PageChoice {
+                page: None,
+                name: String::from("original kept"),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BuiltEpub {
+    pub content: Vec<u8>,
+    pub name: String,
+    pub issues: Vec<Issue>,
 }
 
 #[non_exhaustive]
@@ -20,6 +107,15 @@ pub struct FormatPage {
     pub content: String,
 }
 
+impl FormatPage {
+    pub fn file_name(&self) -> Cow<'_, str> {
+        self.path
+            .file_name()
+            .map(|f| f.to_string_lossy())
+            .unwrap_or_default()
+    }
+}
+
 impl From<(PathBuf, String)> for FormatPage {
     fn from((path, content): (PathBuf, String)) -> Self {
         FormatPage {
@@ -30,8 +126,141 @@ impl From<(PathBuf, String)> for FormatPage {
 }
 
 #[non_exhaustive]
-#[derive(Default, Debug)]
+#[derive(Debug, Clone)]
 pub struct EpubMetadata {
     pub title: String,
+    /// separated by `&`
     pub authors: String,
+    pub publisher: String,
+    pub description: String,
+    pub series: String,
+    pub volume: String,
+    /// separated by `,`
+    pub identifiers: String,
+    /// separated by `,`
+    pub subjects: String,
+    pub date: String,
+    pub language: String,
+}
+
+impl Default for EpubMetadata {
+    fn default() -> Self {
+        EpubMetadata {
+            title: String::new(),
+            authors: String::new(),
+            publisher: String::new(),
+            description: String::new(),
+            series: String::new(),
+            volume: String::new(),
+            identifiers: String::new(),
+            subjects: String::new(),
+            date: String::new(),
+            language: String::from("en"),
+        }
+    }
+}
+
+impl EpubMetadata {
+    /// reads the metadata of the source opf, the language is left as english for the translation
+    pub fn from_source(metadata: &[MetadataItem]) -> Self {
+        let first = |property: &str| {
+            metadata
+                .iter()
+                .find(|e| e.property == property)
+                .map(|e| e.value.trim().to_string())
+                .unwrap_or_default()
+        };
+        let join = |property: &str, separator: &str| {
+            metadata
+                .iter()
+                .filter(|e| e.property == property)
+                .map(|e| e.value.trim())
+                .filter(|e| !e.is_empty())
+                .collect::<Vec<_>>()
+                .join(separator)
+        };
+
+        // calibre writes the series as legacy meta tags, epub3 as a collection
+        let collection = metadata.iter().find(|e| {
+            e.property == "belongs-to-collection"
+                && e.refinement("collection-type")
+                    .is_none_or(|r| r.value == "series")
+        });
+        let (series, volume) = match collection {
+            Some(item) => (
+                item.value.trim().to_string(),
+                item.refinement("group-position")
+                    .map(|r| r.value.trim().to_string())
+                    .unwrap_or_default(),
+            ),
+            None => (first("calibre:series"), first("calibre:series_index")),
+        };
+
+        EpubMetadata {
+            title: first("title"),
+            authors: join("creator", " & "),
+            publisher: first("publisher"),
+            description: first("description"),
+            series,
+            volume,
+            identifiers: join("identifier", ", "),
+            subjects: join("subject", ", "),
+            date: first("date"),
+            ..Default::default()
+        }
+    }
+}
+
+/// an entry of the source table of contents, flattened in order with its depth
+#[derive(Debug, Default, Clone)]
+pub struct TocEntry {
+    /// the normalized path of the page in the source epub
+    pub path: PathBuf,
+    pub fragment: Option<String>,
+    pub level: usize,
+    /// the label in the source epub
+    pub label: String,
+    /// the first heading of the translated page
+    pub heading: Option<String>,
+    /// set when the title is edited in the format view, left empty it falls back
+    pub title: Option<String>,
+}
+
+impl TocEntry {
+    pub fn flatten(points: &[NavPoint], level: usize) -> Vec<TocEntry> {
+        points
+            .iter()
+            .flat_map(|point| {
+                let content = point.content.to_string_lossy();
+                let (path, fragment) = match content.split_once('#') {
+                    Some((path, fragment)) => (path, Some(fragment.to_string())),
+                    None => (content.as_ref(), None),
+                };
+                let entry = TocEntry {
+                    path: normalize_path(path),
+                    fragment,
+                    level,
+                    label: point.label.trim().to_string(),
+                    ..Default::default()
+                };
+                once(entry).chain(TocEntry::flatten(&point.children, level + 1))
+            })
+            .collect()
+    }
+
+    /// the edited title, else the translated heading, else the source label
+    pub fn title(&self) -> &str {
+        self.title
+            .as_deref()
+            .filter(|t| !t.trim().is_empty())
+            .or(self.heading.as_deref())
+            .unwrap_or(&self.label)
+    }
+
+    pub fn file_stem(&self) -> &str {
+        self.path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+    }
 }
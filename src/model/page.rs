@@ -1,4 +1,9 @@
-use crate::{actions::contains_japanese, model::Activity, view::DisplayType};
+use crate::{
+    actions::contains_japanese,
+    controller::notes::{NOTE_MARKER_RE, note_marker},
+    model::Activity,
+    view::DisplayType,
+};
 use iced::{
     Color, Element,
     alignment::Horizontal,
@@ -23,6 +28,8 @@ const FREQUENCY_TOLERANCE: f64 = 10.0;
 const SECTION_CAPACITY: usize = 8 * 1024;
 const MIN_PERCENT: f64 = 70.0;
 const MAX_PERCENT: f64 = 100.0;
+/// back-translations scoring below this chrF against the source are flagged
+const FIDELITY_TOLERANCE: f64 = 0.3;
 
 #[non_exhaustive]
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
@@ -50,7 +57,12 @@ impl Page {
     }
 
     pub fn clear(&mut self) {
-        self.sections.iter_mut().for_each(|e| e.content.clear());
+        self.sections.iter_mut().for_each(|e| {
+            e.content.clear();
+            e.sources.clear();
+            e.notes.clear();
+            e.back_translation = None;
+        });
         self.errors.clear();
     }
 
@@ -90,8 +102,8 @@ impl Page {
             .iter()
             .enumerate()
             .filter(|(_, s)| !s.content.is_empty())
-            .filter_map(|(i, Section { japanese, content })| {
-                let p = (content.len() as f64 / japanese.len() as f64) * 100.0;
+            .filter_map(|(i, section)| {
+                let p = (section.content.len() as f64 / section.japanese.len() as f64) * 100.0;
                 let (min, max) = if i == last {
                     (MIN_PERCENT - 5.0, MAX_PERCENT + 5.0)
                 } else {
@@ -124,12 +136,38 @@ impl Page {
             .collect()
     }
 
+    fn check_fidelity(&self) -> Vec<PageError> {
+        self.sections
+            .iter()
+            .enumerate()
+            .filter_map(|(i, s)| {
+                let back = s.back_translation.as_ref()?;
+                (back.score < FIDELITY_TOLERANCE).then_some(PageError::Fidelity(i))
+            })
+            .collect()
+    }
+
+    /// records a part no candidate has text for, kept by the checks until the page is cleared
+    pub fn mark_missing(&mut self, part: usize) {
+        if !self.missing().any(|e| e.index() == part) {
+            self.errors.push(PageError::Missing(part));
+        }
+    }
+
+    fn missing(&self) -> impl Iterator<Item = &PageError> {
+        self.errors
+            .iter()
+            .filter(|e| matches!(e, PageError::Missing(_)))
+    }
+
     pub fn check_page(&mut self, last_section: &str) {
         self.errors = [
+            self.missing().cloned().collect(),
             self.check_size(),
             self.check_japanese(),
             self.check_frequency(),
             self.check_jaccard(last_section),
+            self.check_fidelity(),
         ]
         .concat();
 
@@ -142,6 +180,28 @@ impl Page {
         };
     }
 
+    pub fn qa_report(&self) -> QaPage<'_> {
+        let comments = self
+            .sections
+            .iter()
+            .enumerate()
+            .flat_map(|(part, section)| {
+                section.open_comments().map(move |(_, comment)| QaComment {
+                    part,
+                    text: &comment.text,
+                    range: comment.range,
+                    excerpt: comment.excerpt(&section.content),
+                })
+            })
+            .collect();
+
+        QaPage {
+            path: &self.path,
+            errors: &self.errors,
+            comments,
+        }
+    }
+
     pub fn error_cards<T: 'static + Clone>(
         &self,
         on_press: impl Fn(usize) -> Option<T> + 'static,
@@ -182,10 +242,44 @@ impl Page {
             .enumerate()
             .flat_map(|(i, section)| {
                 let content = section.span_content(display);
+                let comments = match section.open_comments().count() {
+                    0 => String::new(),
+                    n => format!("\nComments: {}", n),
+                };
+                let scores: String = section
+                    .scores
+                    .iter()
+                    .map(|s| {
+                        format!(
+                            "\n{}: fidelity {} completeness {} fluency {} ({})",
+                            s.name,
+                            s.fidelity,
+                            s.completeness,
+                            s.fluency,
+                            s.total()
+                        )
+                    })
+                    .collect();
+                let fidelity = match &section.back_translation {
+                    Some(back) => format!("\nFidelity: {:.2}", back.score),
+                    None => String::new(),
+                };
+                let sources = match section.sources.is_empty() {
+                    true => String::new(),
+                    false => format!("\nSources: {}", section.sources.join(", ")),
+                };
                 let mut spans = vec![
-                    span(format!("\n\nPart: {}\nCount: {}\n\n", i + 1, content.len()))
-                        .color(color!(0xff0000))
-                        .link_maybe(on_press(i)),
+                    span(format!(
+                        "\n\nPart: {}\nCount: {}{}{}{}{}\n\n",
+                        i + 1,
+                        content.len(),
+                        comments,
+                        scores,
+                        sources,
+                        fidelity
+                    ))
+                    .color(color!(0xff0000))
+                    .link_maybe(on_press(i)),
                 ];
 
                 match display {
@@ -202,7 +296,9 @@ impl Page {
                             );
                         }
                     }
-                    DisplayType::Full | DisplayType::Japanese => spans.push(span(content)),
+                    DisplayType::Full | DisplayType::Japanese | DisplayType::BackTranslation => {
+                        spans.push(span(content))
+                    }
                 }
 
                 spans
@@ -211,7 +307,7 @@ impl Page {
     }
 }
 
-fn jaccard(a: &str, b: &str) -> f64 {
+pub fn jaccard(a: &str, b: &str) -> f64 {
     let set_a: HashSet<&str> = a.split_whitespace().collect();
     let set_b: HashSet<&str> = b.split_whitespace().collect();
     let intersection = set_a.intersection(&set_b).count() as f64;
@@ -278,6 +374,26 @@ fn check_char_frequency(text: &str) -> bool {
 pub struct Section {
     pub japanese: String,
     pub content: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<Comment>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scores: Vec<CandidateScore>,
+    /// the candidate each sentence of the content was taken from by the statistical consensus
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub back_translation: Option<BackTranslation>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<Note>,
+}
+
+/// the content translated back to Japanese and its chrF score against the source
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct BackTranslation {
+    pub text: String,
+    pub score: f64,
 }
 
 impl Section {
@@ -285,7 +401,69 @@ impl Section {
         Self {
             japanese,
             content: String::with_capacity(SECTION_CAPACITY),
+            comments: Vec::new(),
+            suggestions: Vec::new(),
+            scores: Vec::new(),
+            sources: Vec::new(),
+            back_translation: None,
+            notes: Vec::new(),
+        }
+    }
+
+    /// moves the note markers the model left in the content into the notes of the section
+    pub fn capture_notes(&mut self) {
+        if !NOTE_MARKER_RE.is_match(&self.content) {
+            return;
+        }
+        let mut content = String::with_capacity(self.content.len());
+        let mut last = 0;
+        for captures in NOTE_MARKER_RE.captures_iter(&self.content) {
+            let marker = captures.get(0).unwrap();
+            content.push_str(&self.content[last..marker.start()]);
+            last = marker.end();
+
+            let text = captures[1].trim();
+            if !text.is_empty() {
+                let offset = content.chars().count();
+                self.notes.push(Note::new(text.to_string(), Some(offset)));
+            }
         }
+        content.push_str(&self.content[last..]);
+        self.content = content;
+    }
+
+    /// the content with a marker for every note, as it is saved for the format tab
+    pub fn noted_content(&self) -> Cow<'_, str> {
+        if self.notes.is_empty() {
+            return self.content.as_str().into();
+        }
+        let end = self.content.trim_end().chars().count();
+        let mut notes: Vec<_> = self
+            .notes
+            .iter()
+            .map(|note| (note.offset.unwrap_or(end).min(end), note))
+            .collect();
+        notes.sort_by_key(|(offset, _)| *offset);
+
+        let mut notes = notes.into_iter().peekable();
+        let mut content = String::with_capacity(self.content.len());
+        for (i, c) in self.content.chars().enumerate() {
+            while let Some((_, note)) = notes.next_if(|(offset, _)| *offset <= i) {
+                content.push_str(&note_marker(&note.text));
+            }
+            content.push(c);
+        }
+        for (_, note) in notes {
+            content.push_str(&note_marker(&note.text));
+        }
+        content.into()
+    }
+
+    pub fn open_comments(&self) -> impl Iterator<Item = (usize, &Comment)> {
+        self.comments
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| !c.resolved)
     }
 
     pub fn history_message(&self) -> [Message; 2] {
@@ -307,16 +485,98 @@ impl Section {
             }
             DisplayType::Full => self.content.as_str().into(),
             DisplayType::Japanese => self.japanese.as_str().into(),
+            DisplayType::BackTranslation => self
+                .back_translation
+                .as_ref()
+                .map(|back| back.text.as_str())
+                .unwrap_or_default()
+                .into(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Comment {
+    pub text: String,
+    /// character range within `Section::content`, `None` covers the whole section
+    pub range: Option<(usize, usize)>,
+    pub resolved: bool,
+}
+
+impl Comment {
+    pub fn new(text: String, range: Option<(usize, usize)>) -> Self {
+        Self {
+            text,
+            range,
+            resolved: false,
         }
     }
+
+    pub fn excerpt<'a>(&self, content: &'a str) -> Option<&'a str> {
+        let (start, end) = self.range?;
+        let start = content.char_indices().nth(start).map(|(i, _)| i)?;
+        let end = content
+            .char_indices()
+            .nth(end)
+            .map(|(i, _)| i)
+            .unwrap_or(content.len());
+        content.get(start..end)
+    }
 }
 
-#[derive(Debug, Clone)]
+/// a translator note shown as a footnote of the built epub
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Note {
+    pub text: String,
+    /// character offset within `Section::content` the reference is placed at, `None` is the end
+    pub offset: Option<usize>,
+}
+
+impl Note {
+    pub fn new(text: String, offset: Option<usize>) -> Self {
+        Self { text, offset }
+    }
+}
+
+/// rubric scores given to a consensus candidate by the judge
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CandidateScore {
+    pub name: String,
+    pub fidelity: f32,
+    pub completeness: f32,
+    pub fluency: f32,
+}
+
+impl CandidateScore {
+    pub fn total(&self) -> f32 {
+        self.fidelity + self.completeness + self.fluency
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct QaPage<'a> {
+    pub path: &'a PathBuf,
+    pub errors: &'a [PageError],
+    pub comments: Vec<QaComment<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct QaComment<'a> {
+    pub part: usize,
+    pub text: &'a str,
+    pub range: Option<(usize, usize)>,
+    pub excerpt: Option<&'a str>,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub enum PageError {
     Japanese(usize),
     Size(usize),
     Repeat(usize),
     Copy(usize),
+    Fidelity(usize),
+    /// no candidate has text for the part
+    Missing(usize),
 }
 
 impl PageError {
@@ -325,7 +585,20 @@ impl PageError {
             PageError::Japanese(i)
             | PageError::Size(i)
             | PageError::Repeat(i)
-            | PageError::Copy(i) => *i,
+            | PageError::Copy(i)
+            | PageError::Fidelity(i)
+            | PageError::Missing(i) => *i,
+        }
+    }
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            PageError::Japanese(_) => "Japanese",
+            PageError::Size(_) => "Size",
+            PageError::Repeat(_) => "Repeat",
+            PageError::Copy(_) => "Copy",
+            PageError::Fidelity(_) => "Fidelity",
+            PageError::Missing(_) => "Missing",
         }
     }
 
@@ -345,6 +618,8 @@ impl PageError {
             PageError::Size(i) => make_btn(format!("Size error: {:2}", i + 1), *i),
             PageError::Repeat(i) => make_btn(format!("Repeat error: {:2}", i + 1), *i),
             PageError::Copy(i) => make_btn(format!("Copy error: {:2}", i + 1), *i),
+            PageError::Fidelity(i) => make_btn(format!("Fidelity error: {:2}", i + 1), *i),
+            PageError::Missing(i) => make_btn(format!("Missing error: {:2}", i + 1), *i),
         }
     }
 }
@@ -2,9 +2,16 @@ use crate::{
     actions::{
         consensus_action::ConsensusAction, server_action::ServerAction, trans_action::TransAction,
     },
-    controller::client::Client,
+    controller::{
+        client::{Client, SharedHistory},
+        parse::{Judgement, parse_judgement},
+        prompts::{BACK_TRANSLATION_PROMPT, JUDGE_PROMPT},
+    },
     error::{Error, Result},
-    model::page::{Page, Section},
+    model::{
+        consensus::Prompt,
+        page::{CandidateScore, Page, Section},
+    },
 };
 use iced::{Element, Task, task::Handle, widget::pick_list};
 use quick_xml::{Writer, events::BytesText};
@@ -15,6 +22,7 @@ use std::{
     ffi::OsStr,
     io::Cursor,
     iter,
+    ops::Range,
     sync::{Arc, Mutex},
 };
 
@@ -153,21 +161,47 @@ impl Server {
         model: &str,
         page: usize,
     ) -> Result<Task<ConsensusAction>> {
-        let current = pages.last().expect("dont pass an empty array");
+        let (current, pages) = pages.split_last().expect("dont pass an empty array");
         let candidates = candidates
             .get(current.file_stem().unwrap_or_default())
             .ok_or(Error::Error(String::from("missing candidate files")))?;
 
+        let sections: Vec<_> = pages.iter().map(|p| p.sections.as_slice()).collect();
+        let history = self.consensus_history(&sections);
+
         let handles = &mut self.handles;
         let tasks: Result<Vec<_>> = current
             .sections
             .iter()
             .enumerate()
-            .map(|(part, section)| {
-                let candidates: Vec<_> = candidates.iter().flat_map(|e| e.get(part)).collect();
-                let prompt = consensus_prompt(&section.japanese, &candidates)?;
-                self.client
-                    .consensus(prompt, model.to_string(), self.settings.think, page, part)
+            .filter_map(|(part, section)| {
+                // parts without candidate text are left out, the page marks them missing
+                let candidates = part_candidates(candidates, part);
+                if candidates.is_empty() {
+                    return None;
+                }
+                let prompt = match consensus_prompt(&section.japanese, &candidates) {
+                    Ok(prompt) => prompt,
+                    Err(error) => return Some(Err(error)),
+                };
+                Some(match &history {
+                    Some(history) => self.client.consensus_history(
+                        prompt,
+                        section.japanese.clone(),
+                        model.to_string(),
+                        history.clone(),
+                        &self.settings,
+                        page,
+                        part,
+                    ),
+                    None => self.client.consensus(
+                        prompt,
+                        model.to_string(),
+                        self.settings.think,
+                        page,
+                        part,
+                    ),
+                })
             })
             .map(|task| task.map(|task| bind(handles, task)))
             .collect();
@@ -183,20 +217,166 @@ impl Server {
         page: usize,
         part: usize,
     ) -> Result<Task<ConsensusAction>> {
-        let current = pages.last().expect("dont pass an empty array");
-        let section = current.sections.get(part).unwrap();
+        let (current, pages) = pages.split_last().expect("dont pass an empty array");
+        let (section, current_sections) = current
+            .sections
+            .get(..part + 1)
+            .and_then(|p| p.split_last())
+            .unwrap();
         let page_candidates = candidates
             .get(&current.file_stem().unwrap_or_default())
             .ok_or(Error::Error(String::from("missing candidate file")))?;
-        let page_candidates: Vec<_> = page_candidates.iter().flat_map(|e| e.get(part)).collect();
+        let page_candidates = part_candidates(page_candidates, part);
+        // left out as in `consensus`, the page marks the part missing
+        if page_candidates.is_empty() {
+            return Ok(Task::none());
+        }
         let prompt = consensus_prompt(&section.japanese, &page_candidates)?;
 
-        let task =
-            self.client
-                .consensus(prompt, model.to_string(), self.settings.think, page, part)?;
+        let sections: Vec<_> = pages
+            .iter()
+            .map(|p| p.sections.as_slice())
+            .chain(iter::once(current_sections))
+            .collect();
+
+        let task = match self.consensus_history(&sections) {
+            Some(history) => self.client.consensus_history(
+                prompt,
+                section.japanese.clone(),
+                model,
+                history,
+                &self.settings,
+                page,
+                part,
+            )?,
+            None => self
+                .client
+                .consensus(prompt, model, self.settings.think, page, part)?,
+        };
 
         Ok(self.bind_handle(task))
     }
+
+    /// previously synthesized sections as chat history, only used by the history method
+    fn consensus_history(&self, sections: &[&[Section]]) -> Option<SharedHistory> {
+        matches!(self.method, Method::History).then(|| {
+            let history = build_history(sections, self.settings.context_window);
+            Arc::new(Mutex::new(history))
+        })
+    }
+
+    /// scores the named candidates of the sections in `parts` with the judge rubric
+    pub fn judge(
+        &mut self,
+        current: &Page,
+        candidates: &[(&str, &[String])],
+        model: &str,
+        page: usize,
+        parts: Range<usize>,
+    ) -> Result<Task<ConsensusAction>> {
+        let handles = &mut self.handles;
+        let tasks: Result<Vec<_>> = current
+            .sections
+            .iter()
+            .enumerate()
+            .skip(parts.start)
+            .take(parts.len())
+            .map(|(part, section)| {
+                let (names, texts): (Vec<_>, Vec<_>) = candidates
+                    .iter()
+                    .filter_map(|(name, parts)| Some((name.to_string(), parts.get(part)?)))
+                    .unzip();
+                let prompt = consensus_prompt(&section.japanese, &texts)?;
+                let task =
+                    self.client
+                        .complete(JUDGE_PROMPT, prompt, model, self.settings.think)?;
+
+                Ok(task.then(move |response| {
+                    let scores = response
+                        .and_then(|response| parse_judgement(&response))
+                        .map(|judgement| judged_scores(judgement, &names));
+                    match scores {
+                        Ok(scores) => Task::done(ConsensusAction::SetScores { page, part, scores }),
+                        Err(error) => error.display_error(),
+                    }
+                }))
+            })
+            .map(|task| task.map(|task| bind(handles, task)))
+            .collect();
+
+        Ok(self.method.join_tasks(tasks?))
+    }
+
+    /// generates the candidate translation of every section in the page with each generator
+    pub fn generate_candidates(
+        &mut self,
+        current: &Page,
+        generators: &[(usize, String, Prompt)],
+        page: usize,
+    ) -> Result<Task<ConsensusAction>> {
+        let handles = &mut self.handles;
+        let tasks: Result<Vec<_>> = generators
+            .iter()
+            .flat_map(|(candidate, model, prompt)| {
+                current
+                    .sections
+                    .iter()
+                    .enumerate()
+                    .map(move |(part, section)| (*candidate, model, *prompt, part, section))
+            })
+            .map(|(candidate, model, prompt, part, section)| {
+                let task = self.client.complete(
+                    prompt.preamble(),
+                    section.japanese.clone(),
+                    model,
+                    self.settings.think,
+                )?;
+                Ok(task.then(move |content| match content {
+                    Ok(content) => Task::done(ConsensusAction::SetCandidatePart {
+                        candidate,
+                        page,
+                        part,
+                        content,
+                    }),
+                    Err(error) => error.display_error(),
+                }))
+            })
+            .map(|task| task.map(|task| bind(handles, task)))
+            .collect();
+
+        Ok(self.method.join_tasks(tasks?))
+    }
+
+    /// translates every part with content back to Japanese for the fidelity check
+    pub fn back_translate(
+        &mut self,
+        current: &Page,
+        model: &str,
+        page: usize,
+    ) -> Result<Task<TransAction>> {
+        let handles = &mut self.handles;
+        let tasks: Result<Vec<_>> = current
+            .sections
+            .iter()
+            .enumerate()
+            .filter(|(_, section)| !section.content.trim().is_empty())
+            .map(|(part, section)| {
+                let task = self.client.complete(
+                    BACK_TRANSLATION_PROMPT,
+                    section.content.clone(),
+                    model,
+                    self.settings.think,
+                )?;
+                Ok(task.then(move |text| match text {
+                    Ok(text) => Task::done(TransAction::SetBackTranslation { page, part, text }),
+                    Err(error) => error.display_error(),
+                }))
+            })
+            .map(|task| task.map(|task| bind(handles, task)))
+            .collect();
+
+        Ok(self.method.join_tasks(tasks?))
+    }
 }
 
 fn bind<T: 'static>(handles: &mut Vec<Handle>, task: Task<T>) -> Task<T> {
@@ -205,6 +385,21 @@ fn bind<T: 'static>(handles: &mut Vec<Handle>, task: Task<T>) -> Task<T> {
     task
 }
 
+fn judged_scores(judgement: Judgement, names: &[String]) -> Vec<CandidateScore> {
+    judgement
+        .scores
+        .into_iter()
+        .filter_map(|score| {
+            Some(CandidateScore {
+                name: names.get(score.candidate)?.clone(),
+                fidelity: score.fidelity,
+                completeness: score.completeness,
+                fluency: score.fluency,
+            })
+        })
+        .collect()
+}
+
 fn build_history(sections: &[&[Section]], context_window: usize) -> Vec<Message> {
     let mut recent: Vec<_> = sections
         .iter()
@@ -221,6 +416,16 @@ fn build_history(sections: &[&[Section]], context_window: usize) -> Vec<Message>
         .collect()
 }
 
+/// the candidate texts of a part, a candidate without text for it, such as a generation that
+/// failed or a gap in a loaded translation, is left out
+pub fn part_candidates<'a>(candidates: &[&'a [String]], part: usize) -> Vec<&'a String> {
+    candidates
+        .iter()
+        .filter_map(|e| e.get(part))
+        .filter(|text| !text.trim().is_empty())
+        .collect()
+}
+
 pub fn consensus_prompt(section: &str, candidates: &[&String]) -> Result<String> {
     let mut writer = Writer::new_with_indent(Cursor::new(Vec::new()), b' ', 2);
 
@@ -1,5 +1,6 @@
 use crate::{
     actions::trans_action::TransAction,
+    controller::{structure::TextMode, xml::RubyPolicy},
     model::{page::Page, server::Server},
     view::DisplayType,
     widget::page_sidebar::{SidebarAction, SidebarDeps, SidebarRow},
@@ -14,6 +15,70 @@ pub struct Translation {
     pub current_page: usize,
     pub pages: Vec<Page>,
     pub display: DisplayType,
+    pub comment: CommentDraft,
+    pub note: NoteDraft,
+    /// the model used for the back-translation fidelity check
+    pub back_model: Option<String>,
+    pub ruby: RubyPolicy,
+    pub text_mode: TextMode,
+}
+
+#[derive(Debug)]
+pub struct CommentDraft {
+    pub part: usize,
+    pub range: String,
+    pub text: String,
+}
+
+impl Default for CommentDraft {
+    fn default() -> Self {
+        Self {
+            part: 1,
+            range: String::new(),
+            text: String::new(),
+        }
+    }
+}
+
+impl CommentDraft {
+    /// parses the range input as `start-end` character offsets, empty covers the whole part
+    pub fn range(&self) -> Option<Option<(usize, usize)>> {
+        let range = self.range.trim();
+        if range.is_empty() {
+            return Some(None);
+        }
+        let (start, end) = range.split_once('-')?;
+        let start = start.trim().parse().ok()?;
+        let end = end.trim().parse().ok()?;
+        (start < end).then_some(Some((start, end)))
+    }
+}
+
+#[derive(Debug)]
+pub struct NoteDraft {
+    pub part: usize,
+    pub offset: String,
+    pub text: String,
+}
+
+impl Default for NoteDraft {
+    fn default() -> Self {
+        Self {
+            part: 1,
+            offset: String::new(),
+            text: String::new(),
+        }
+    }
+}
+
+impl NoteDraft {
+    /// parses the offset input as a character offset, empty places the note at the end of the part
+    pub fn offset(&self) -> Option<Option<usize>> {
+        match self.offset.trim() {
+            "" => Some(None),
+            offset => offset.parse().ok().map(Some),
+        }
+    }
 }
 
 impl Translation {
@@ -1,5 +1,8 @@
 use crate::{
-    actions::trans_action::TransAction,
+    actions::{
+        consensus_action::{ConsensusAction, alignment_report},
+        trans_action::TransAction,
+    },
     message::Message,
     model::{consensus::Consensus, doc::Doc, format::Format, translation::Translation},
     view::View,
@@ -34,8 +37,13 @@ impl Default for Translator {
 }
 
 impl Translator {
-    pub fn set_view(&mut self, view: View) {
+    pub fn set_view(&mut self, view: View) -> Task<Message> {
+        let task = match view {
+            View::Consensus => self.sync_candidates(),
+            _ => Task::none(),
+        };
         self.view = view;
+        task
     }
 
     pub fn toggle_side_bar_collapse(&mut self) {
@@ -58,6 +66,7 @@ impl Translator {
 
     pub fn close_tab(&mut self, tab: usize) {
         self.translations.remove(&tab);
+        self.consensus.tab_changed(tab);
 
         if self.translations.contains_key(&self.active_tab) {
             return;
@@ -70,7 +79,30 @@ impl Translator {
         }
     }
 
+    /// live candidates of changed tabs are synced before every action except the streamed ones,
+    /// which never read them, and after it for the tabs the action added
+    pub fn consensus_action(&mut self, action: ConsensusAction) -> Task<Message> {
+        let streamed = matches!(
+            action,
+            ConsensusAction::ServerAction(_)
+                | ConsensusAction::UpdateContent { .. }
+                | ConsensusAction::SetCandidatePart { .. }
+        );
+        if streamed {
+            return self.consensus.perform(action).map(Into::into);
+        }
+        let synced = self.sync_candidates();
+        let task = self.consensus.perform(action).map(Into::into);
+        Task::batch([synced, task, self.sync_candidates()])
+    }
+
+    fn sync_candidates(&mut self) -> Task<Message> {
+        let report = self.consensus.sync_tabs(&self.translations);
+        alignment_report(report).map(Into::into)
+    }
+
     pub fn translation_action(&mut self, tab: usize, action: TransAction) -> Task<Message> {
+        self.consensus.tab_changed(tab);
         match self.translations.get_mut(&tab) {
             Some(model) => model.perform(action).map(Message::TransAction.with(tab)),
             None => Task::none(),
@@ -1,48 +1,70 @@
 use crate::{
-    actions::{consensus_action::ConsensusAction, server_action::ServerAction},
+    actions::consensus_action::ConsensusAction,
     model::{
-        consensus::Consensus,
-        server::{Method, Server},
+        consensus::{Consensus, ConsensusMode, Prompt},
+        server::Server,
+        translation::Translation,
     },
-    view::{menu_button, rich_text_scrollable},
+    view::{menu_button, rich_text_scrollable, ruby_selector, text_mode_selector},
     widget::{
         page_sidebar::build_path_buttons,
-        server_widget::{ollama_input, think_selector},
+        server_widget::{context_window_input, execution_selector, ollama_input, think_selector},
     },
 };
 use iced::{
     Border, Color, Element, Length, Padding, Renderer, Theme,
     alignment::Vertical,
     widget::{
-        Container, button, column, container, lazy, radio, row, scrollable, space::vertical, stack,
-        text,
+        Container, button, button::Status, column, container, lazy, pick_list, radio, row,
+        scrollable, space::vertical, stack, text,
     },
 };
 use iced_aw::{Menu, MenuBar, menu::Item};
-use std::ops::Not;
+use std::{collections::BTreeMap, iter::once, ops::Not};
 
-pub fn consensus_view(model: &Consensus) -> Element<'_, ConsensusAction> {
+pub fn consensus_view<'a>(
+    model: &'a Consensus,
+    tabs: &'a BTreeMap<usize, Translation>,
+) -> Element<'a, ConsensusAction> {
     let page = model.current_page();
     let current_page = model.current_page;
+    let mode = model.mode;
     let can_consensus = page.is_some_and(|page| {
-        !page.active() && model.server.connected() && !model.file_name().is_empty()
+        !page.active()
+            && (model.server.connected() || mode == ConsensusMode::Statistical)
+            && !model.file_name().is_empty()
     });
     let on_press = move |part| {
-        can_consensus.then_some(ConsensusAction::ConsensusPart {
-            page: current_page,
-            part,
+        can_consensus.then_some(match mode {
+            ConsensusMode::Synthesize => ConsensusAction::ConsensusPart {
+                page: current_page,
+                part,
+            },
+            ConsensusMode::Judge => ConsensusAction::JudgePart {
+                page: current_page,
+                part,
+            },
+            ConsensusMode::Statistical => ConsensusAction::MergePart {
+                page: current_page,
+                part,
+            },
         })
     };
 
-    let error_cards = page.map(|p| p.error_cards(on_press));
-    let content = page
-        .map(|p| p.spans(model.display, on_press))
-        .unwrap_or_default();
+    let error_cards = page
+        .filter(|_| model.diff.is_none())
+        .map(|p| p.error_cards(on_press));
+    let content = match &model.diff {
+        Some(diff) => diff.spans(),
+        None => page
+            .map(|p| p.spans(model.display, on_press))
+            .unwrap_or_default(),
+    };
 
     container(column![
         vertical(),
         column![
-            menu_bar(model),
+            menu_bar(model, tabs),
             row![
                 side_bar(model),
                 stack![rich_text_scrollable(content), error_cards]
@@ -77,16 +99,23 @@ fn side_bar(model: &Consensus) -> Container<'_, ConsensusAction> {
         })
 }
 
-fn menu_bar(model @ Consensus { server, .. }: &Consensus) -> Element<'_, ConsensusAction> {
+fn menu_bar<'a>(
+    model @ Consensus { server, .. }: &'a Consensus,
+    tabs: &'a BTreeMap<usize, Translation>,
+) -> Element<'a, ConsensusAction> {
     row![
         MenuBar::new(vec![
             epub_menu(model),
-            candidate_menu(model),
+            candidate_menu(model, tabs),
+            generate_menu(model),
+            diff_menu(model),
             server_menu(server)
         ])
         .spacing(5),
         consensus_button(model),
+        keep_top_button(model),
         server.model_pick_list().map(Into::into),
+        mode_selector(model),
     ]
     .width(Length::Fill)
     .spacing(5)
@@ -97,13 +126,17 @@ fn menu_bar(model @ Consensus { server, .. }: &Consensus) -> Element<'_, Consens
 fn consensus_button(model: &Consensus) -> Element<'_, ConsensusAction> {
     let (button_text, message) = if !model.server.handles.is_empty() {
         ("cancel", Some(ConsensusAction::CancelConsensus))
-    } else if !model.server.connected()
+    } else if !(model.server.connected() || model.mode == ConsensusMode::Statistical)
         || model.file_name().is_empty()
         || model.candidates.is_empty()
     {
         ("translate", None)
     } else {
-        let msg = ConsensusAction::Consensus(model.current_page);
+        let msg = match model.mode {
+            ConsensusMode::Synthesize => ConsensusAction::Consensus(model.current_page),
+            ConsensusMode::Judge => ConsensusAction::Judge(model.current_page),
+            ConsensusMode::Statistical => ConsensusAction::Merge,
+        };
         ("translate", Some(msg))
     };
 
@@ -112,6 +145,36 @@ fn consensus_button(model: &Consensus) -> Element<'_, ConsensusAction> {
         .into()
 }
 
+/// the judge only scores the candidates, the top scored ones replace the content on request
+fn keep_top_button(model: &Consensus) -> Element<'_, ConsensusAction> {
+    let judged = model
+        .pages
+        .iter()
+        .flat_map(|p| &p.sections)
+        .any(|s| !s.scores.is_empty());
+    let message =
+        (model.server.handles.is_empty() && judged).then_some(ConsensusAction::KeepTopScored);
+
+    button(text("keep top scored").center())
+        .on_press_maybe(message)
+        .into()
+}
+
+fn mode_selector(model: &Consensus) -> Element<'_, ConsensusAction> {
+    let selection = [
+        ("Synthesize", ConsensusMode::Synthesize),
+        ("Judge", ConsensusMode::Judge),
+        ("Statistical", ConsensusMode::Statistical),
+    ];
+    let radio_buttons = selection
+        .into_iter()
+        .map(|(l, m)| radio(l, m, Some(model.mode), ConsensusAction::SetMode).into());
+
+    container(row![text("Mode:")].extend(radio_buttons).spacing(10))
+        .padding(5)
+        .into()
+}
+
 fn server_menu(state: &Server) -> Item<'_, ConsensusAction, Theme, Renderer> {
     Item::with_menu(
         menu_button("server"),
@@ -119,40 +182,23 @@ fn server_menu(state: &Server) -> Item<'_, ConsensusAction, Theme, Renderer> {
             Item::new(ollama_input().map(Into::into)),
             Item::new(think_selector(state).map(Into::into)),
             Item::new(execution_selector(state).map(Into::into)),
+            Item::new(context_window_input(state).map(Into::into)),
         ])
         .spacing(10)
         .width(400),
     )
 }
 
-pub fn execution_selector(state: &Server) -> Element<'_, ServerAction> {
-    container(
-        row![
-            text("Execution:"),
-            radio(
-                "Chain",
-                Method::Chain,
-                Some(state.method),
-                ServerAction::SetMethod
-            ),
-            radio(
-                "Batch",
-                Method::Batch,
-                Some(state.method),
-                ServerAction::SetMethod
-            ),
-        ]
-        .spacing(10),
-    )
-    .align_left(Length::Fill)
-    .into()
-}
-
 fn epub_menu(model: &Consensus) -> Item<'_, ConsensusAction, Theme, Renderer> {
     Item::with_menu(
         menu_button("epub"),
         Menu::new(vec![
             Item::new(epub_select(model)),
+            Item::new(ruby_selector(model.ruby, ConsensusAction::SetRuby)),
+            Item::new(text_mode_selector(
+                model.text_mode,
+                ConsensusAction::SetTextMode,
+            )),
             Item::new(save_button(model)),
         ])
         .spacing(10)
@@ -164,11 +210,18 @@ fn save_button(model: &Consensus) -> Element<'_, ConsensusAction> {
     let file_name = model.file_name();
     let not_empty = file_name.is_empty().not();
     let save_message = not_empty.then_some(ConsensusAction::SaveTranslation(file_name));
+    let recover_message = not_empty.then_some(ConsensusAction::Recover);
 
-    button(text("save").center())
-        .on_press_maybe(save_message)
-        .padding(5)
-        .into()
+    row![
+        button(text("save").center())
+            .on_press_maybe(save_message)
+            .padding(5),
+        button(text("recover").center())
+            .on_press_maybe(recover_message)
+            .padding(5)
+    ]
+    .spacing(10)
+    .into()
 }
 
 fn epub_select(model: &Consensus) -> Element<'_, ConsensusAction> {
@@ -189,10 +242,121 @@ fn epub_select(model: &Consensus) -> Element<'_, ConsensusAction> {
     .into()
 }
 
-fn candidate_menu(model: &Consensus) -> Item<'_, ConsensusAction, Theme, Renderer> {
+fn candidate_menu<'a>(
+    model: &'a Consensus,
+    tabs: &'a BTreeMap<usize, Translation>,
+) -> Item<'a, ConsensusAction, Theme, Renderer> {
+    let open_tabs = tabs
+        .iter()
+        .filter(|(tab, _)| model.candidates.iter().all(|c| c.tab != Some(**tab)))
+        .map(|(&tab, translation)| {
+            Item::new(
+                row![
+                    button(text("tab").center()).on_press(ConsensusAction::AddTab(tab)),
+                    text(format!("{} {}", tab + 1, translation.file_name())),
+                ]
+                .align_y(Vertical::Center)
+                .spacing(10),
+            )
+        });
+
     Item::with_menu(
         menu_button("candidate"),
-        Menu::new(model.candidate_items())
+        Menu::new(
+            model
+                .candidate_items()
+                .into_iter()
+                .chain(open_tabs)
+                .collect(),
+        )
+        .spacing(10)
+        .width(400)
+        .padding(10),
+    )
+}
+
+fn generate_menu(model: &Consensus) -> Item<'_, ConsensusAction, Theme, Renderer> {
+    let generators = model.generators.iter().enumerate().map(|(i, generator)| {
+        Item::new(
+            row![
+                pick_list(
+                    model.server.models.as_slice(),
+                    generator.model.as_ref(),
+                    move |model| ConsensusAction::SetGeneratorModel(i, model)
+                )
+                .width(Length::Fill),
+                pick_list(Prompt::ALL, Some(generator.prompt), move |prompt| {
+                    ConsensusAction::SetGeneratorPrompt(i, prompt)
+                }),
+                button(text("x").center())
+                    .style(|theme, status| match status {
+                        Status::Hovered => button::primary(theme, status),
+                        _ => button::text(theme, status),
+                    })
+                    .on_press(ConsensusAction::DropGenerator(i)),
+            ]
+            .align_y(Vertical::Center)
+            .spacing(10),
+        )
+    });
+
+    let can_generate = model.server.connected()
+        && model.server.handles.is_empty()
+        && !model.file_name().is_empty()
+        && model.generators.iter().any(|g| g.model.is_some());
+    let generate = can_generate.then_some(ConsensusAction::Generate(model.current_page));
+
+    let controls = row![
+        button(text("add model").center()).on_press(ConsensusAction::AddGenerator),
+        button(text("generate").center()).on_press_maybe(generate),
+    ]
+    .spacing(10);
+
+    Item::with_menu(
+        menu_button("generate"),
+        Menu::new(generators.chain(once(Item::new(controls))).collect())
+            .spacing(10)
+            .width(400)
+            .padding(10),
+    )
+}
+
+fn diff_menu(model: &Consensus) -> Item<'_, ConsensusAction, Theme, Renderer> {
+    let sections = model.current_page().map_or(0, |p| p.sections.len());
+    let parts: Vec<usize> = (1..=sections).collect();
+    let part = model.diff.as_ref().map(|diff| diff.part + 1);
+    let reference = model.diff.as_ref().map(|diff| diff.reference.clone());
+
+    let controls = row![
+        text("Part:"),
+        pick_list(parts, part, |part| ConsensusAction::ShowDiff(Some(
+            part - 1
+        ))),
+        button(text("close").center()).on_press_maybe(
+            model
+                .diff
+                .is_some()
+                .then_some(ConsensusAction::ShowDiff(None))
+        ),
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10);
+
+    let reference = row![
+        text("Against:"),
+        pick_list(
+            model.diff_references(),
+            reference,
+            ConsensusAction::SetDiffReference
+        )
+        .width(Length::Fill),
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10);
+
+    Item::with_menu(
+        menu_button("diff"),
+        Menu::new(vec![Item::new(controls), Item::new(reference)])
             .spacing(10)
             .width(400)
             .padding(10),
@@ -1,4 +1,9 @@
-use crate::{actions::doc_action::DocAction, model::doc::Doc, view::text_scrollable};
+use crate::{
+    actions::doc_action::DocAction,
+    controller::xml::RubyPolicy,
+    model::doc::Doc,
+    view::{ruby_selector, text_scrollable},
+};
 use iced::widget::{button, column, container, pick_list, row, text};
 use iced::{
     Element, Length, Padding,
@@ -9,9 +14,18 @@ use iced::{
 pub fn doc_view(model: &Doc) -> Element<'_, DocAction> {
     container(column![
         vertical(),
-        column![epub_select_button(), text_scrollable(&model.content),]
-            .height(Length::FillPortion(9))
-            .padding(10),
+        column![
+            row![
+                epub_select_button(),
+                ruby_selector(model.ruby, DocAction::SetRuby)
+            ]
+            .spacing(10),
+            row![text_scrollable(&model.content)]
+                .push(readings(model))
+                .spacing(10),
+        ]
+        .height(Length::FillPortion(9))
+        .padding(10),
         page_selector(model).height(Length::FillPortion(1))
     ])
     .center_x(Length::Fill)
@@ -22,6 +36,19 @@ pub fn doc_view(model: &Doc) -> Element<'_, DocAction> {
     .into()
 }
 
+/// the reading list of the book, only shown when readings are collected
+fn readings(model: &Doc) -> Option<Container<'_, DocAction>> {
+    if model.ruby != RubyPolicy::Collect {
+        return None;
+    }
+    let readings: String = model
+        .readings
+        .iter()
+        .map(|(base, reading)| format!("{base}: {reading}\n"))
+        .collect();
+    Some(text_scrollable(readings).width(250))
+}
+
 pub fn page_selector(model: &Doc) -> Container<'_, DocAction> {
     container(row![
         button(text("◀")).on_press(DocAction::Dec),
@@ -1,12 +1,22 @@
-use crate::{actions::format_action::FormatAction, model::format::Format};
+use crate::{
+    actions::{format_action::FormatAction, server_action::ServerAction},
+    controller::{
+        style::{ParagraphStyle, StyleTheme},
+        typography::{EllipsisStyle, QuoteStyle},
+    },
+    model::format::{BuiltEpub, Format},
+    view::text_scrollable,
+};
 use iced::alignment::Horizontal;
-use iced::widget::{Column, image, text_input};
+use iced::widget::{Column, checkbox, image, pick_list, text_input};
 use iced::widget::{button, column, container, row, scrollable, space::vertical, text};
 use iced::{
     Border, Color, Element, Length, Padding,
     alignment::Vertical,
+    color,
     widget::{Row, container::transparent},
 };
+use std::iter::once;
 
 pub fn format_view(model: &Format) -> Element<'_, FormatAction> {
     let build = model
@@ -15,17 +25,27 @@ pub fn format_view(model: &Format) -> Element<'_, FormatAction> {
         .filter(|_| !model.pages.is_empty())
         .map(|_| FormatAction::Build);
 
-    container(column![
-        vertical(),
-        column![
-            format_menu_bar(model),
+    let content = match (&model.built, &model.typography_preview) {
+        (Some(built), _) => build_report(built),
+        (None, Some(preview)) => typography_preview(preview),
+        (None, None) => column![
             row![epub_image(model), epub_metadata(model)].spacing(10),
-            container(button(text("build").center()).on_press_maybe(build))
-                .align_right(Length::Fill)
-                .padding(20)
+            row![
+                style_options(model),
+                button(text("build").center()).on_press_maybe(build)
+            ]
+            .align_y(Vertical::Center)
+            .spacing(20)
+            .padding(20)
         ]
-        .height(Length::FillPortion(9))
-        .padding(10),
+        .into(),
+    };
+
+    container(column![
+        vertical(),
+        column![format_menu_bar(model), content]
+            .height(Length::FillPortion(9))
+            .padding(10),
         vertical(),
     ])
     .center_x(Length::Fill)
@@ -36,6 +56,114 @@ pub fn format_view(model: &Format) -> Element<'_, FormatAction> {
     .into()
 }
 
+/// the theme and typography of the built epub
+fn style_options(model @ Format { style, .. }: &Format) -> Element<'_, FormatAction> {
+    column![
+        row![
+            text("Theme:"),
+            pick_list(StyleTheme::ALL, Some(style.theme), FormatAction::SetTheme),
+            button(text("load css").center()).on_press(FormatAction::LoadCss),
+            text("Paragraphs:"),
+            pick_list(
+                ParagraphStyle::ALL,
+                Some(style.paragraphs),
+                FormatAction::SetParagraphs
+            ),
+            text_input("font family", &style.font_family).on_input(FormatAction::SetFontFamily),
+        ]
+        .align_y(Vertical::Center)
+        .spacing(10),
+        row![
+            text("Scene break:"),
+            text_input("keep rules", &style.scene_break)
+                .on_input(FormatAction::SetSceneBreak)
+                .width(120),
+            checkbox(style.justify)
+                .label("justify")
+                .on_toggle(FormatAction::SetJustify),
+            checkbox(style.keep_source_styles)
+                .label("keep source styles")
+                .on_toggle(FormatAction::SetKeepSourceStyles),
+        ]
+        .align_y(Vertical::Center)
+        .spacing(10),
+        typography_options(model),
+    ]
+    .width(Length::Fill)
+    .spacing(10)
+    .into()
+}
+
+/// the punctuation normalized before building
+fn typography_options(model: &Format) -> Element<'_, FormatAction> {
+    let typography = &model.style.typography;
+    let preview = (!model.pages.is_empty()).then_some(FormatAction::PreviewTypography);
+    row![
+        pick_list(
+            QuoteStyle::ALL,
+            Some(typography.quotes),
+            FormatAction::SetQuotes
+        ),
+        checkbox(typography.nested_single)
+            .label("『』 as single quotes")
+            .on_toggle(FormatAction::SetNestedSingle),
+        pick_list(
+            EllipsisStyle::ALL,
+            Some(typography.ellipsis),
+            FormatAction::SetEllipsis
+        ),
+        checkbox(typography.dashes)
+            .label("em dashes")
+            .on_toggle(FormatAction::SetDashes),
+        checkbox(typography.full_width)
+            .label("full-width")
+            .on_toggle(FormatAction::SetFullWidth),
+        checkbox(typography.wave_dash)
+            .label("wave dashes")
+            .on_toggle(FormatAction::SetWaveDash),
+        checkbox(typography.brackets)
+            .label("【】")
+            .on_toggle(FormatAction::SetBrackets),
+        button(text("preview").center()).on_press_maybe(preview),
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10)
+    .into()
+}
+
+/// the changes of the typography pass, nothing is modified until the epub is built
+fn typography_preview(preview: &str) -> Element<'_, FormatAction> {
+    column![
+        text_scrollable(preview),
+        row![button(text("close").center()).on_press(FormatAction::ClosePreview)].padding(20)
+    ]
+    .align_x(Horizontal::Right)
+    .into()
+}
+
+/// the validation report of the last build, saving is left to the user
+fn build_report(BuiltEpub { name, issues, .. }: &BuiltEpub) -> Element<'_, FormatAction> {
+    let report = match issues.len() {
+        0 => format!("{name}: no issues found"),
+        count => {
+            let issues: String = issues.iter().map(|issue| format!("{issue}\n")).collect();
+            format!("{name}: {count} issue(s) found\n\n{issues}")
+        }
+    };
+
+    column![
+        text_scrollable(report),
+        row![
+            button(text("discard").center()).on_press(FormatAction::DiscardBuilt),
+            button(text("save").center()).on_press(FormatAction::SaveBuilt),
+        ]
+        .spacing(10)
+        .padding(20)
+    ]
+    .align_x(Horizontal::Right)
+    .into()
+}
+
 fn format_menu_bar(model: &Format) -> Row<'_, FormatAction> {
     row![epub_button(model), content_button(model),]
         .width(Length::Fill)
@@ -100,23 +228,27 @@ fn epub_image(model: &Format) -> Element<'_, FormatAction> {
 }
 
 fn epub_metadata(model @ Format { metadata, .. }: &Format) -> Element<'_, FormatAction> {
-    let label_width = 80;
     let content = column![
+        metadata_input("Title", &metadata.title, FormatAction::SetTitle),
+        metadata_input("Author(s)", &metadata.authors, FormatAction::SetAuthors),
+        metadata_input("Publisher", &metadata.publisher, FormatAction::SetPublisher),
         row![
-            container(text("Title: "))
-                .align_right(Length::Fill)
-                .width(label_width),
-            text_input("Title", &metadata.title).on_input(FormatAction::SetTitle)
-        ]
-        .align_y(Vertical::Center),
+            metadata_input("Series", &metadata.series, FormatAction::SetSeries),
+            metadata_input("Volume", &metadata.volume, FormatAction::SetVolume),
+        ],
         row![
-            container(text("Author(s): "))
-                .align_right(Length::Fill)
-                .width(label_width),
-            text_input("Author(s)", &metadata.authors).on_input(FormatAction::SetAuthors)
-        ]
-        .align_y(Vertical::Center),
-        content_files(model)
+            metadata_input("Date", &metadata.date, FormatAction::SetDate),
+            metadata_input("Language", &metadata.language, FormatAction::SetLanguage),
+        ],
+        metadata_input(
+            "Identifiers",
+            &metadata.identifiers,
+            FormatAction::SetIdentifiers
+        ),
+        metadata_input("Subjects", &metadata.subjects, FormatAction::SetSubjects),
+        description_input(model),
+        description_translation(model),
+        row![page_mapping(model), toc_titles(model)].spacing(10)
     ]
     .spacing(5);
     container(content)
@@ -128,12 +260,122 @@ fn epub_metadata(model @ Format { metadata, .. }: &Format) -> Element<'_, Format
         .into()
 }
 
+fn metadata_input<'a>(
+    label: &str,
+    value: &'a str,
+    on_input: impl Fn(String) -> FormatAction + 'a,
+) -> Element<'a, FormatAction> {
+    row![
+        container(text(format!("{label}: ")))
+            .align_right(Length::Fill)
+            .width(90),
+        text_input(label, value).on_input(on_input)
+    ]
+    .align_y(Vertical::Center)
+    .into()
+}
+
+/// the description is locked while it is being translated
+fn description_input(
+    Format {
+        server, metadata, ..
+    }: &Format,
+) -> Element<'_, FormatAction> {
+    let on_input = server
+        .handles
+        .is_empty()
+        .then_some(FormatAction::SetDescription);
+    row![
+        container(text("Description: "))
+            .align_right(Length::Fill)
+            .width(90),
+        text_input("Description", &metadata.description).on_input_maybe(on_input)
+    ]
+    .align_y(Vertical::Center)
+    .into()
+}
+
+/// translates the description with the selected model of the connected server, a running
+/// translation can be stopped
+fn description_translation(
+    Format {
+        server, metadata, ..
+    }: &Format,
+) -> Element<'_, FormatAction> {
+    let translate = (server.connected()
+        && server.handles.is_empty()
+        && server.current_model.is_some()
+        && !metadata.description.trim().is_empty())
+    .then_some(FormatAction::TranslateDescription);
+
+    row![
+        button(text("connect").center()).on_press(ServerAction::Connect.into()),
+        pick_list(
+            server.models.as_slice(),
+            server.current_model.as_ref(),
+            |model| ServerAction::SelectModel(model).into()
+        )
+        .placeholder("model")
+        .width(Length::Fill),
+        match server.handles.is_empty() {
+            true => button(text("translate description").center()).on_press_maybe(translate),
+            false => button(text("stop").center()).on_press(ServerAction::Abort.into()),
+        },
+    ]
+    .align_y(Vertical::Center)
+    .padding(Padding::new(0.0).left(90))
+    .spacing(10)
+    .into()
+}
+
+/// every spine item with the translation replacing it, translations left unmapped are flagged
+fn page_mapping(model: &Format) -> Element<'_, FormatAction> {
+    if model.epub.is_none() {
+        return content_files(model);
+    }
+
+    let choices = model.page_choices();
+    let kept = model.mapping.iter().filter(|m| m.page.is_none()).count();
+    let orphans: Vec<_> = model.orphans().collect();
+    let summary = text(format!(
+        "{} translated, {kept} original kept, {} orphan(s)",
+        model.mapping.len() - kept,
+        orphans.len()
+    ));
+
+    let items = model.mapping.iter().enumerate().map(|(i, mapping)| {
+        row![
+            text(mapping.file_name()).width(Length::Fill),
+            pick_list(
+                choices.clone(),
+                Some(model.page_choice(mapping)),
+                move |choice| FormatAction::SetMapping(i, choice.page)
+            )
+            .width(Length::Fill),
+        ]
+        .align_y(Vertical::Center)
+        .spacing(10)
+        .into()
+    });
+    let orphans = orphans.into_iter().map(|page| {
+        text(format!("orphan: {}", page.file_name()))
+            .color(color!(0xff0000))
+            .into()
+    });
+    let pages: Column<_> = once(summary.into()).chain(items).chain(orphans).collect();
+    file_list(pages)
+}
+
 fn content_files(Format { pages, .. }: &Format) -> Element<'_, FormatAction> {
     let pages: Column<_> = pages
         .iter()
         .filter_map(|p| p.path.file_stem())
         .map(|p| text(p.to_string_lossy()).into())
         .collect();
+    file_list(pages)
+}
+
+fn file_list(pages: Column<'_, FormatAction>) -> Element<'_, FormatAction> {
     container(
         scrollable(
             pages
@@ -155,3 +397,43 @@ fn content_files(Format { pages, .. }: &Format) -> Element<'_, FormatAction> {
     })
     .into()
 }
+
+/// the source toc with an editable title per entry, indented by depth
+fn toc_titles(Format { toc, .. }: &Format) -> Element<'_, FormatAction> {
+    let entries: Column<_> = toc
+        .iter()
+        .enumerate()
+        .map(|(i, entry)| {
+            let indent = (entry.level.saturating_sub(1) * 20) as f32;
+            container(
+                text_input(
+                    entry.title(),
+                    entry.title.as_deref().unwrap_or(entry.title()),
+                )
+                .on_input(move |title| FormatAction::SetTocTitle(i, title)),
+            )
+            .padding(Padding::new(0.0).left(indent))
+            .into()
+        })
+        .collect();
+    container(
+        scrollable(
+            entries
+                .padding(Padding::new(5.0).horizontal(10))
+                .width(Length::Fill)
+                .spacing(5),
+        )
+        .height(Length::Fill),
+    )
+    .height(Length::Fill)
+    .width(Length::Fill)
+    .padding(Padding::new(10.0).left(0).right(5))
+    .style(|theme| {
+        transparent(theme).border(Border {
+            color: Color::WHITE,
+            width: 1.0,
+            radius: 8.into(),
+        })
+    })
+    .into()
+}
@@ -1,4 +1,5 @@
-use iced::widget::{button, container, rich_text, scrollable, span, text};
+use crate::controller::{structure::TextMode, xml::RubyPolicy};
+use iced::widget::{button, container, pick_list, rich_text, row, scrollable, span, text};
 use iced::{
     Border, Color, Element, Length, Padding,
     alignment::{Horizontal, Vertical},
@@ -93,6 +94,34 @@ pub fn part_span(i: usize, content: String) -> [Span<'static>; 2] {
     [span(text).color(color!(0xff0000)), span(content)]
 }
 
+pub fn ruby_selector<'a, E: Clone + 'a>(
+    policy: RubyPolicy,
+    on_select: impl Fn(RubyPolicy) -> E + 'a,
+) -> Element<'a, E> {
+    row![
+        text("Ruby:"),
+        pick_list(RubyPolicy::ALL, Some(policy), on_select).width(Length::Fill)
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10)
+    .padding(5)
+    .into()
+}
+
+pub fn text_mode_selector<'a, E: Clone + 'a>(
+    mode: TextMode,
+    on_select: impl Fn(TextMode) -> E + 'a,
+) -> Element<'a, E> {
+    row![
+        text("Text:"),
+        pick_list(TextMode::ALL, Some(mode), on_select).width(Length::Fill)
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10)
+    .padding(5)
+    .into()
+}
+
 pub fn menu_button<'a, T: 'a>(button_text: &str) -> Button<'_, T> {
     button(text(button_text).center()).style(|theme, status| match status {
         Status::Disabled => primary(theme, Status::Active),
@@ -106,4 +135,6 @@ pub enum DisplayType {
     Full,
     End,
     Japanese,
+    /// the back-translation of the fidelity check
+    BackTranslation,
 }
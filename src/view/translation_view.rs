@@ -2,21 +2,25 @@ use crate::{
     actions::trans_action::TransAction,
     message::Message,
     model::{server::Server, translation::Translation},
-    view::{DisplayType, menu_button, rich_text_scrollable},
+    view::{DisplayType, menu_button, rich_text_scrollable, ruby_selector, text_mode_selector},
     widget::{
         context_menu_button,
         page_sidebar::build_path_buttons,
         server_widget::{context_window_input, execution_selector, ollama_input, think_selector},
     },
 };
-use iced::widget::{button, column, container, row, scrollable, text};
+use iced::widget::{
+    Column, button, column, container, pick_list, row, scrollable, text, text_input,
+};
 use iced::{
     Border, Color, Element, Function, Length, Padding, Renderer, Theme,
     alignment::Vertical,
     border::Radius,
     widget::{Button, Container, Row, container::transparent, lazy, space::vertical, stack},
 };
-use iced_aw::{ContextMenu, Menu, MenuBar, TabBar, card::Status, menu::Item, style::tab_bar};
+use iced_aw::{
+    ContextMenu, Menu, MenuBar, NumberInput, TabBar, card::Status, menu::Item, style::tab_bar,
+};
 use std::collections::BTreeMap;
 
 pub fn translation_view(
@@ -93,23 +97,30 @@ fn tab(model: &Translation) -> Element<'_, TransAction> {
             menu_bar(model),
             row![
                 side_bar(model),
-                stack![
-                    ContextMenu::new(rich_text_scrollable(content), || container(column![
-                        context_menu_button(text("full").color(Color::WHITE))
-                            .on_press(TransAction::SetDisplay(DisplayType::Full))
-                            .width(Length::Fill),
-                        context_menu_button(text("end").color(Color::WHITE))
-                            .on_press(TransAction::SetDisplay(DisplayType::End))
-                            .width(Length::Fill),
-                        context_menu_button(text("japanese").color(Color::WHITE))
-                            .on_press(TransAction::SetDisplay(DisplayType::Japanese))
-                            .width(Length::Fill)
-                    ])
-                    .style(container::rounded_box)
-                    .width(100)
-                    .into()),
-                    error_cards
+                column![
+                    stack![
+                        ContextMenu::new(rich_text_scrollable(content), || container(column![
+                            context_menu_button(text("full").color(Color::WHITE))
+                                .on_press(TransAction::SetDisplay(DisplayType::Full))
+                                .width(Length::Fill),
+                            context_menu_button(text("end").color(Color::WHITE))
+                                .on_press(TransAction::SetDisplay(DisplayType::End))
+                                .width(Length::Fill),
+                            context_menu_button(text("japanese").color(Color::WHITE))
+                                .on_press(TransAction::SetDisplay(DisplayType::Japanese))
+                                .width(Length::Fill),
+                            context_menu_button(text("back").color(Color::WHITE))
+                                .on_press(TransAction::SetDisplay(DisplayType::BackTranslation))
+                                .width(Length::Fill)
+                        ])
+                        .style(container::rounded_box)
+                        .width(100)
+                        .into()),
+                        error_cards
+                    ],
+                    comment_panel(model)
                 ]
+                .spacing(10)
             ]
             .spacing(10)
         ]
@@ -125,6 +136,143 @@ fn tab(model: &Translation) -> Element<'_, TransAction> {
     .into()
 }
 
+fn comment_panel(model: &Translation) -> Element<'_, TransAction> {
+    let page = model.current_page;
+    let comments: Column<_> = model
+        .current_page()
+        .into_iter()
+        .flat_map(|p| p.sections.iter().enumerate())
+        .flat_map(|(part, section)| {
+            section.open_comments().map(move |(i, comment)| {
+                let excerpt = comment
+                    .excerpt(&section.content)
+                    .map(|e| format!(" \"{}\"", e))
+                    .unwrap_or_default();
+                row![
+                    text!("Part {}:{} {}", part + 1, excerpt, comment.text).width(Length::Fill),
+                    button(text("resolve").center()).padding(5).on_press(
+                        TransAction::ResolveComment {
+                            page,
+                            part,
+                            comment: i,
+                        }
+                    )
+                ]
+                .align_y(Vertical::Center)
+                .spacing(10)
+                .into()
+            })
+        })
+        .collect();
+
+    let suggestions = model
+        .current_page()
+        .into_iter()
+        .flat_map(|p| p.sections.iter().enumerate())
+        .flat_map(|(part, section)| {
+            section
+                .suggestions
+                .iter()
+                .enumerate()
+                .map(move |(i, suggestion)| {
+                    row![
+                        text!("Part {} memory: {}", part + 1, suggestion).width(Length::Fill),
+                        button(text("use").center()).padding(5).on_press(
+                            TransAction::UseSuggestion {
+                                page,
+                                part,
+                                suggestion: i,
+                            }
+                        )
+                    ]
+                    .align_y(Vertical::Center)
+                    .spacing(10)
+                    .into()
+                })
+        });
+    let notes = model
+        .current_page()
+        .into_iter()
+        .flat_map(|p| p.sections.iter().enumerate())
+        .flat_map(|(part, section)| {
+            section.notes.iter().enumerate().map(move |(i, note)| {
+                let offset = note
+                    .offset
+                    .map(|offset| format!(" @{offset}"))
+                    .unwrap_or_default();
+                row![
+                    text!("Part {} note{}: {}", part + 1, offset, note.text).width(Length::Fill),
+                    button(text("remove").center())
+                        .padding(5)
+                        .on_press(TransAction::RemoveNote {
+                            page,
+                            part,
+                            note: i,
+                        })
+                ]
+                .align_y(Vertical::Center)
+                .spacing(10)
+                .into()
+            })
+        });
+    let comments = comments.extend(suggestions).extend(notes);
+
+    let count = model.current_page().map(|p| p.sections.len()).unwrap_or(1);
+    let add = (model.current_page().is_some() && !model.comment.text.is_empty())
+        .then_some(TransAction::AddComment(page));
+
+    let input = row![
+        text("Part:"),
+        NumberInput::new(&model.comment.part, 1..=count, TransAction::SetCommentPart),
+        text_input("start-end", &model.comment.range)
+            .on_input(TransAction::SetCommentRange)
+            .width(100),
+        text_input("comment", &model.comment.text)
+            .on_input(TransAction::SetCommentText)
+            .on_submit_maybe(add.clone()),
+        button(text("add").center()).on_press_maybe(add),
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10);
+
+    let add_note = (model.current_page().is_some() && !model.note.text.is_empty())
+        .then_some(TransAction::AddNote(page));
+
+    let note_input = row![
+        text("Part:"),
+        NumberInput::new(&model.note.part, 1..=count, TransAction::SetNotePart),
+        text_input("offset", &model.note.offset)
+            .on_input(TransAction::SetNoteOffset)
+            .width(100),
+        text_input("translator note", &model.note.text)
+            .on_input(TransAction::SetNoteText)
+            .on_submit_maybe(add_note.clone()),
+        button(text("note").center()).on_press_maybe(add_note),
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10);
+
+    container(
+        column![
+            scrollable(comments.spacing(5).width(Length::Fill)).height(Length::Shrink),
+            input,
+            note_input
+        ]
+        .spacing(10),
+    )
+    .max_height(200)
+    .width(Length::Fill)
+    .padding(10)
+    .style(|theme| {
+        transparent(theme).border(Border {
+            color: Color::WHITE,
+            width: 1.0,
+            radius: 8.into(),
+        })
+    })
+    .into()
+}
+
 fn side_bar(model: &Translation) -> Container<'_, TransAction> {
     let buttons = lazy(model.sidebar_deps(), |deps| {
         build_path_buttons(deps).width(250).spacing(10)
@@ -148,7 +296,12 @@ fn menu_bar(
     }: &Translation,
 ) -> Row<'_, TransAction> {
     row![
-        MenuBar::new(vec![epub_menu(model), server_menu(server_state)]).spacing(5),
+        MenuBar::new(vec![
+            epub_menu(model),
+            qa_menu(model),
+            server_menu(server_state)
+        ])
+        .spacing(5),
         translate_button(model),
         server_state.model_pick_list().map(Into::into),
     ]
@@ -170,6 +323,35 @@ fn translate_button(model: &Translation) -> Button<'_, TransAction> {
     button(text(button_text).center()).on_press_maybe(message)
 }
 
+fn qa_menu(model: &Translation) -> Item<'_, TransAction, Theme, Renderer> {
+    let can_check = model.server.connected()
+        && model.server.handles.is_empty()
+        && model.back_model.is_some()
+        && model.current_page().is_some();
+    let back_translate = can_check.then_some(TransAction::BackTranslate(model.current_page));
+
+    let controls = row![
+        pick_list(
+            model.server.models.as_slice(),
+            model.back_model.as_ref(),
+            TransAction::SetBackModel
+        )
+        .placeholder("back-translation model")
+        .width(Length::Fill),
+        button(text("check").center()).on_press_maybe(back_translate),
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10);
+
+    Item::with_menu(
+        menu_button("qa"),
+        Menu::new(vec![Item::new(controls)])
+            .padding(10)
+            .spacing(10)
+            .width(400),
+    )
+}
+
 fn server_menu(state: &Server) -> Item<'_, TransAction, Theme, Renderer> {
     Item::with_menu(
         menu_button("server"),
@@ -190,6 +372,11 @@ fn epub_menu(model: &Translation) -> Item<'_, TransAction, Theme, Renderer> {
         menu_button("epub"),
         Menu::new(vec![
             Item::new(epub_select(model)),
+            Item::new(ruby_selector(model.ruby, TransAction::SetRuby)),
+            Item::new(text_mode_selector(
+                model.text_mode,
+                TransAction::SetTextMode,
+            )),
             Item::new(file_menu_buttons(model)),
         ])
         .spacing(10)
@@ -200,20 +387,66 @@ fn epub_menu(model: &Translation) -> Item<'_, TransAction, Theme, Renderer> {
 fn file_menu_buttons(state: &Translation) -> Element<'_, TransAction> {
     let file_name = state.file_name();
     let not_empty = !file_name.is_empty();
-    let save = not_empty.then_some(TransAction::SaveTranslation(file_name));
+    let save = not_empty.then_some(TransAction::SaveTranslation(file_name.clone()));
     let recovery = not_empty.then_some(TransAction::Recover);
+    let qa = not_empty.then_some(TransAction::ExportQa(file_name.clone()));
+    let export_xliff = not_empty.then_some(TransAction::ExportXliff(file_name.clone()));
+    let import_xliff = not_empty.then_some(TransAction::ImportXliff);
+    let export_tmx = not_empty.then_some(TransAction::ExportTmx(file_name.clone()));
+    let import_tmx = not_empty.then_some(TransAction::ImportTmx);
+    let export_csv = not_empty.then_some(TransAction::ExportCsv(file_name));
+    let import_csv = not_empty.then_some(TransAction::ImportCsv);
 
-    row![
+    let file = row![
         button(text("save").center())
             .on_press_maybe(save)
             .padding(5),
         button(text("recover").center())
             .on_press_maybe(recovery)
+            .padding(5),
+        button(text("export qa").center())
+            .on_press_maybe(qa)
             .padding(5)
     ]
     .align_y(Vertical::Center)
-    .spacing(10)
-    .into()
+    .spacing(10);
+
+    let exchange = row![
+        button(text("export xliff").center())
+            .on_press_maybe(export_xliff)
+            .padding(5),
+        button(text("import xliff").center())
+            .on_press_maybe(import_xliff)
+            .padding(5)
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10);
+
+    let memory = row![
+        button(text("export tmx").center())
+            .on_press_maybe(export_tmx)
+            .padding(5),
+        button(text("import tmx").center())
+            .on_press_maybe(import_tmx)
+            .padding(5)
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10);
+
+    let spreadsheet = row![
+        button(text("export csv").center())
+            .on_press_maybe(export_csv)
+            .padding(5),
+        button(text("import csv").center())
+            .on_press_maybe(import_csv)
+            .padding(5)
+    ]
+    .align_y(Vertical::Center)
+    .spacing(10);
+
+    column![file, exchange, memory, spreadsheet]
+        .spacing(10)
+        .into()
 }
 
 fn epub_select(model: &Translation) -> Row<'_, TransAction> {